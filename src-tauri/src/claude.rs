@@ -10,12 +10,15 @@ use tokio::sync::Mutex;
 /// Global registry of running query processes, keyed by query_id.
 pub type ProcessRegistry = Arc<Mutex<HashMap<String, Child>>>;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct QueryConfig {
     pub message: String,
     pub model: Option<String>,
     pub mcp_config: Option<String>,
     pub system_prompt: Option<String>,
+    /// Added to Claude's built-in system prompt (`--append-system-prompt`) instead of replacing it.
+    #[serde(default)]
+    pub append_system_prompt: Option<String>,
     pub session_id: Option<String>,
     pub resume: bool,
     /// "claude" or "gemini" — determines which CLI to spawn
@@ -24,7 +27,14 @@ pub struct QueryConfig {
     pub max_turns: Option<u32>,
     /// Control built-in tool availability.
     /// None = default (all tools), Some("") = disable all, Some("Bash,Read") = specific tools only.
+    /// Deprecated: ignored when `allowed_tools` or `disallowed_tools` is set.
     pub tools: Option<String>,
+    /// Claude CLI --allowedTools: tools usable without a permission prompt.
+    #[serde(default)]
+    pub allowed_tools: Option<Vec<String>>,
+    /// Claude CLI --disallowedTools: tools removed from the model's reach (e.g. ["Bash"]).
+    #[serde(default)]
+    pub disallowed_tools: Option<Vec<String>>,
     /// When true, ignore user's default MCP config — only use servers from mcp_config field.
     /// Combined with tools="" this creates a "pure reasoning" mode with zero tool access.
     #[serde(default)]
@@ -36,6 +46,75 @@ pub struct QueryConfig {
     /// Working directory for the CLI process. Set by send_query from the active project root.
     #[serde(default)]
     pub cwd: Option<String>,
+    /// ThunderClaude session (sessions/<id>.json) this query continues.
+    /// Used to refuse queries into read-only (shared) sessions.
+    #[serde(default)]
+    pub local_session_id: Option<String>,
+    /// Always pipe the message via stdin, whatever its length.
+    #[serde(default)]
+    pub force_stdin: Option<bool>,
+    /// Byte length above which the message is piped via stdin (None = STDIN_PIPE_THRESHOLD).
+    #[serde(default)]
+    pub stdin_threshold: Option<usize>,
+    /// Kill the CLI if it hasn't finished after this many seconds (None = no limit).
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Emit a `claude-spawn` event describing the exact command before running it.
+    #[serde(default)]
+    pub debug: Option<bool>,
+    /// Extra CLI flags, passed verbatim after the known flags (caller is responsible
+    /// for their correctness). Flags that control prompt input or output format are
+    /// dropped, since the wrapper relies on them (see `RESERVED_FLAGS`).
+    #[serde(default)]
+    pub extra_args: Option<Vec<String>>,
+    /// Extra environment variables for the CLI process (e.g. ANTHROPIC_BASE_URL per project).
+    /// Variables the wrapper strips itself (see `RESERVED_ENV_VARS`) are ignored.
+    #[serde(default)]
+    pub env: Option<HashMap<String, String>>,
+    /// Build the command but don't run it: `claude-done` carries it as `command`.
+    #[serde(default)]
+    pub dry_run: Option<bool>,
+    /// Internal run the chat shouldn't show (e.g. session compaction): events
+    /// go out as `claude-background-message` / `-raw` / `-error` / `-spawn` / `-done`.
+    #[serde(default)]
+    pub background: Option<bool>,
+}
+
+/// Values the Claude CLI accepts for --permission-mode.
+const PERMISSION_MODES: &[&str] = &["default", "acceptEdits", "bypassPermissions", "plan"];
+
+impl QueryConfig {
+    /// Check invariants the CLI would otherwise only reject on stderr after
+    /// spawning. Every violation is reported as `field: problem`, joined by "; ".
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        if let Some(ref engine) = self.engine {
+            if engine != "claude" && engine != "gemini" {
+                errors.push(format!("engine: unknown engine \"{}\" (expected claude or gemini)", engine));
+            }
+        }
+        if self.resume && self.session_id.as_deref().is_none_or(|s| s.trim().is_empty()) {
+            errors.push("session_id: required when resume is true".to_string());
+        }
+        if let Some(ref mode) = self.permission_mode {
+            if !PERMISSION_MODES.contains(&mode.as_str()) {
+                errors.push(format!(
+                    "permission_mode: unknown mode \"{}\" (expected one of {})",
+                    mode,
+                    PERMISSION_MODES.join(", ")
+                ));
+            }
+        }
+        if self.max_turns == Some(0) {
+            errors.push("max_turns: must be at least 1".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Invalid query: {}", errors.join("; ")))
+        }
+    }
 }
 
 /// Get the user's home directory (cross-platform).
@@ -45,8 +124,61 @@ fn home_dir() -> String {
         .unwrap_or_default()
 }
 
-/// Find the Claude CLI binary (cross-platform).
+/// Last concrete (non-PATH-fallback) discoveries, reused while they still exist on disk.
+/// VS Code extension updates delete old version dirs, so a stale entry is re-discovered.
+static CLAUDE_BINARY_CACHE: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+static GEMINI_BINARY_CACHE: std::sync::Mutex<Option<(String, Vec<String>)>> =
+    std::sync::Mutex::new(None);
+
+/// CLI paths configured in Settings, tried before auto-discovery.
+static CLAUDE_BINARY_OVERRIDE: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+static GEMINI_BINARY_OVERRIDE: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+
+/// Apply `claude_binary_path` / `gemini_binary_path` from Settings (blank = discover).
+pub fn set_binary_overrides(claude: Option<String>, gemini: Option<String>) {
+    let clean = |p: Option<String>| p.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    *CLAUDE_BINARY_OVERRIDE.lock().unwrap() = clean(claude);
+    *GEMINI_BINARY_OVERRIDE.lock().unwrap() = clean(gemini);
+}
+
+/// The configured path for `name`, if it still points at an executable file.
+fn usable_override(slot: &std::sync::Mutex<Option<String>>, name: &str) -> Option<String> {
+    let path = slot.lock().unwrap().clone()?;
+    let meta = std::fs::metadata(&path).ok().filter(|m| m.is_file());
+    #[cfg(unix)]
+    let meta = {
+        use std::os::unix::fs::PermissionsExt;
+        meta.filter(|m| m.permissions().mode() & 0o111 != 0)
+    };
+    if meta.is_none() {
+        eprintln!(
+            "Warning: Configured {} binary {} is missing or not executable; falling back to discovery",
+            name, path
+        );
+        return None;
+    }
+    Some(path)
+}
+
+/// Find the Claude CLI binary: the configured path, else the cached discovery
+/// when still valid, else a fresh discovery.
 fn find_claude_binary() -> String {
+    if let Some(bin) = usable_override(&CLAUDE_BINARY_OVERRIDE, "Claude") {
+        return bin;
+    }
+    let mut cache = CLAUDE_BINARY_CACHE.lock().unwrap();
+    if let Some(ref bin) = *cache {
+        if std::path::Path::new(bin).exists() {
+            return bin.clone();
+        }
+    }
+    let bin = discover_claude_binary();
+    *cache = if bin == "claude" { None } else { Some(bin.clone()) };
+    bin
+}
+
+/// Search the known install locations for the Claude CLI binary (cross-platform).
+fn discover_claude_binary() -> String {
     let home = home_dir();
 
     // ── Windows ────────────────────────────────────────────────────────────
@@ -180,9 +312,198 @@ pub fn check_claude_available() -> String {
     find_claude_binary()
 }
 
-/// Find the Gemini CLI binary (cross-platform).
-/// Returns (executable, pre_args) — either node + script path, or wrapper/fallback.
+#[derive(Serialize)]
+pub struct ClaudeVersion {
+    pub path: String,
+    /// First line of `claude --version` ("" when it couldn't be determined)
+    pub version: String,
+}
+
+/// Resolve the Claude CLI and ask it for its version. A binary that exists but
+/// fails `--version` still reports its path, with an empty version.
+pub async fn claude_version() -> ClaudeVersion {
+    let path = find_claude_binary();
+    let mut cmd = if path.ends_with(".cmd") {
+        let mut c = Command::new("cmd.exe");
+        c.arg("/c").arg(&path);
+        c
+    } else {
+        Command::new(&path)
+    };
+    cmd.arg("--version")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+
+    #[cfg(target_os = "macos")]
+    cmd.env("PATH", effective_path());
+
+    #[cfg(target_os = "windows")]
+    {
+        #[allow(unused_imports)]
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let version = match tokio::time::timeout(std::time::Duration::from_secs(10), cmd.output()).await {
+        Ok(Ok(out)) if out.status.success() => String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        _ => String::new(),
+    };
+    ClaudeVersion { path, version }
+}
+
+/// Public wrapper so lib.rs can pre-resolve the Gemini CLI during startup warm-up.
+pub fn check_gemini_available() -> String {
+    find_gemini_binary().0
+}
+
+/// How long the login shell gets to print its PATH before we give up and use
+/// the inherited one (a slow or interactive rc file would otherwise hang startup).
+#[cfg(target_os = "macos")]
+const LOGIN_SHELL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// PATH reported by the user's login shell (macOS only, resolved once).
+/// GUI launches on macOS inherit a minimal PATH from launchd, so CLIs installed
+/// via nvm/Homebrew/npm prefixes resolve in the terminal but not in the app.
+#[cfg(target_os = "macos")]
+fn login_shell_path() -> Option<String> {
+    static CACHE: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
+    CACHE
+        .get_or_init(|| {
+            use std::io::Read;
+            let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/zsh".to_string());
+            let mut child = std::process::Command::new(shell)
+                .args(["-ilc", "printf '__TC_PATH__%s' \"$PATH\""])
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+
+            // Read on a thread so a stuck shell can't block us past the deadline
+            let mut stdout = child.stdout.take()?;
+            let (tx, rx) = std::sync::mpsc::channel();
+            std::thread::spawn(move || {
+                let mut buf = Vec::new();
+                let _ = stdout.read_to_end(&mut buf);
+                let _ = tx.send(buf);
+            });
+            let out = match rx.recv_timeout(LOGIN_SHELL_TIMEOUT) {
+                Ok(out) => out,
+                Err(_) => {
+                    eprintln!("Warning: login shell PATH lookup timed out; using the inherited PATH");
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return None;
+                }
+            };
+            let _ = child.wait();
+
+            let stdout = String::from_utf8_lossy(&out);
+            let path = stdout.rsplit("__TC_PATH__").next()?.trim().to_string();
+            if path.is_empty() { None } else { Some(path) }
+        })
+        .clone()
+}
+
+/// The PATH spawned CLIs get: the app's own PATH, with the login shell's PATH
+/// entries prepended on macOS.
+pub fn effective_path() -> String {
+    let app_path = std::env::var("PATH").unwrap_or_default();
+    #[cfg(target_os = "macos")]
+    if let Some(shell_path) = login_shell_path() {
+        let mut entries: Vec<std::path::PathBuf> = std::env::split_paths(&shell_path).collect();
+        for p in std::env::split_paths(&app_path) {
+            if !entries.contains(&p) {
+                entries.push(p);
+            }
+        }
+        if let Ok(joined) = std::env::join_paths(entries) {
+            return joined.to_string_lossy().to_string();
+        }
+    }
+    app_path
+}
+
+/// Resolve `name` against a PATH string the way the OS would (`which`).
+fn which_in(path: &str, name: &str) -> Option<String> {
+    let exts: &[&str] = if cfg!(target_os = "windows") { &[".exe", ".cmd", ".bat"] } else { &[""] };
+    for dir in std::env::split_paths(path) {
+        for ext in exts {
+            let candidate = dir.join(format!("{}{}", name, ext));
+            if candidate.is_file() {
+                return Some(candidate.to_string_lossy().to_string());
+            }
+        }
+    }
+    None
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathDiagnosis {
+    /// Effective PATH passed to spawned CLIs
+    pub path: String,
+    /// PATH the app process itself was launched with
+    pub app_path: String,
+    pub shell: Option<String>,
+    /// What each tool resolves to within the effective PATH (None = not found)
+    pub claude_in_path: Option<String>,
+    pub gemini_in_path: Option<String>,
+    pub node_in_path: Option<String>,
+    /// What `run_query` would actually spawn (known install locations first)
+    pub claude_binary: String,
+    pub gemini_binary: String,
+}
+
+/// Report the PATH the app would use and whether the CLIs resolve within it,
+/// for "works in terminal, not in app" troubleshooting.
+pub fn diagnose_path() -> PathDiagnosis {
+    let path = effective_path();
+    PathDiagnosis {
+        claude_in_path: which_in(&path, "claude"),
+        gemini_in_path: which_in(&path, "gemini"),
+        node_in_path: which_in(&path, "node"),
+        app_path: std::env::var("PATH").unwrap_or_default(),
+        shell: std::env::var("SHELL").ok().or_else(|| std::env::var("COMSPEC").ok()),
+        claude_binary: find_claude_binary(),
+        gemini_binary: find_gemini_binary().0,
+        path,
+    }
+}
+
+/// Find the Gemini CLI binary: the configured path (a `.js` entry script runs
+/// under node), else the cached discovery when still valid, else a fresh one.
 fn find_gemini_binary() -> (String, Vec<String>) {
+    if let Some(bin) = GEMINI_BINARY_OVERRIDE.lock().unwrap().clone() {
+        if bin.ends_with(".js") && std::path::Path::new(&bin).is_file() {
+            return ("node".to_string(), vec![bin]);
+        }
+    }
+    if let Some(bin) = usable_override(&GEMINI_BINARY_OVERRIDE, "Gemini") {
+        return (bin, vec![]);
+    }
+    let mut cache = GEMINI_BINARY_CACHE.lock().unwrap();
+    if let Some((ref bin, ref pre_args)) = *cache {
+        // node + script: the script is what can disappear; wrappers are checked directly
+        let probe = pre_args.first().unwrap_or(bin);
+        if std::path::Path::new(probe).exists() {
+            return (bin.clone(), pre_args.clone());
+        }
+    }
+    let found = discover_gemini_binary();
+    *cache = if found.0 == "gemini" { None } else { Some(found.clone()) };
+    found
+}
+
+/// Search the known install locations for the Gemini CLI (cross-platform).
+/// Returns (executable, pre_args) — either node + script path, or wrapper/fallback.
+fn discover_gemini_binary() -> (String, Vec<String>) {
     let home = home_dir();
 
     // ── Windows: prefer node.exe + script directly (bypasses .cmd issues with CREATE_NO_WINDOW)
@@ -247,114 +568,424 @@ fn find_gemini_binary() -> (String, Vec<String>) {
     ("gemini".to_string(), vec![])
 }
 
-/// Run a query using either Claude or Gemini CLI and stream output as events
-pub async fn run_query(app: &AppHandle, query_id: &str, config: QueryConfig, registry: ProcessRegistry) -> Result<String, String> {
-    let engine = config.engine.as_deref().unwrap_or("claude");
-    let is_gemini = engine == "gemini";
+/// Stderr lines kept per query for `diagnose_failure`.
+const STDERR_TAIL_LINES: usize = 50;
 
-    let (binary, pre_args) = if is_gemini {
-        find_gemini_binary()
+/// A user-facing explanation of why a query failed.
+#[derive(Serialize, Clone, Debug)]
+pub struct FailureDiagnosis {
+    /// auth | rate_limit | model_not_found | mcp | network | trust | not_installed | unknown
+    pub category: String,
+    pub message: String,
+    /// Last few raw stderr lines, for the details view
+    pub tail: String,
+}
+
+/// Pattern-match common CLI failures in stderr output into a concise, actionable
+/// message. Falls back to the raw tail when nothing matches.
+pub fn diagnose_failure(stderr_lines: &[String]) -> FailureDiagnosis {
+    let tail = stderr_lines
+        .iter()
+        .rev()
+        .take(8)
+        .rev()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    let haystack = stderr_lines.join("\n").to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|n| haystack.contains(n));
+
+    // Match the CLIs' own error prefixes and status phrases — bare codes like
+    // "401" or generic OS errors also show up in ordinary tool output.
+    let (category, message) = if has(&["api error: 401", "status 401", "status: 401", "\"code\": 401",
+        "authentication_error", "invalid api key", "invalid x-api-key", "oauth token has expired",
+        "please run /login", "not logged in", "unauthenticated"]) {
+        ("auth", "Authentication failed or expired. Run `claude /login` (or re-authenticate the Gemini CLI) and try again.")
+    } else if has(&["api error: 429", "status 429", "status: 429", "\"code\": 429", "rate_limit_error",
+        "rate limit exceeded", "resource_exhausted", "overloaded_error", "api error: 529", "usage limit reached",
+        "quota exceeded"]) {
+        ("rate_limit", "Rate or usage limit reached. Wait a bit, or switch to another model or engine.")
+    } else if has(&["model not found", "unknown model", "invalid model", "model_not_found"])
+        || (haystack.contains("not_found_error") && haystack.contains("model"))
+    {
+        ("model_not_found", "The selected model isn't available for this account or CLI version. Pick another model.")
+    } else if has(&["mcp server", "mcp error", "mcp config", "failed to start mcp", "mcp-config"]) {
+        ("mcp", "An MCP server failed to start or crashed. Check its command in the MCP settings, or disable it.")
+    } else if has(&["econnrefused", "enotfound", "etimedout", "econnreset", "getaddrinfo", "socket hang up",
+        "fetch failed", "network error", "connection reset"]) {
+        ("network", "Network error reaching the API. Check your connection, proxy, or VPN settings.")
+    } else if has(&["trust the files", "trust this folder", "do you trust"]) {
+        ("trust", "The CLI is waiting for a trust-directory confirmation. Run the CLI once in this folder from a terminal and accept.")
+    } else if has(&["is not recognized as an internal", "command not found", "env: node: no such file or directory",
+        "cannot find module"]) {
+        ("not_installed", "The CLI could not be started. Check the installation (see PATH diagnostics in Settings).")
     } else {
-        (find_claude_binary(), vec![])
+        ("unknown", "The query failed. See the error output for details.")
     };
 
-    let is_cmd = binary.ends_with(".cmd");
-    let mut cmd = if is_cmd {
-        let mut c = Command::new("cmd.exe");
-        c.arg("/c").arg(&binary);
-        for arg in &pre_args {
-            c.arg(arg);
-        }
-        c
-    } else {
-        let mut c = Command::new(&binary);
-        for arg in &pre_args {
-            c.arg(arg);
+    FailureDiagnosis {
+        category: category.to_string(),
+        message: message.to_string(),
+        tail,
+    }
+}
+
+// ── Query timings ───────────────────────────────────────────────────────────
+
+/// Completed query timings kept on disk (oldest dropped first).
+const MAX_QUERY_TIMINGS: usize = 500;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryTiming {
+    pub query_id: String,
+    pub engine: String,
+    pub model: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub exit_code: i32,
+    /// Total tokens reported by the CLI's result message, if any
+    pub token_count: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningQuery {
+    pub query_id: String,
+    pub engine: String,
+    pub model: Option<String>,
+    pub started_at: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryTimings {
+    pub running: Vec<RunningQuery>,
+    /// Most recent first
+    pub completed: Vec<QueryTiming>,
+}
+
+static RUNNING_QUERIES: std::sync::LazyLock<std::sync::Mutex<HashMap<String, RunningQuery>>> =
+    std::sync::LazyLock::new(Default::default);
+/// Keeps a query listed in `RUNNING_QUERIES` until dropped, so early returns,
+/// panics and cancelled futures never leave a stale entry behind.
+struct RunningQueryGuard(String);
+
+impl RunningQueryGuard {
+    fn register(query: RunningQuery) -> Self {
+        let id = query.query_id.clone();
+        RUNNING_QUERIES.lock().unwrap().insert(id.clone(), query);
+        Self(id)
+    }
+}
+
+impl Drop for RunningQueryGuard {
+    fn drop(&mut self) {
+        RUNNING_QUERIES.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Serializes read-modify-write of the timings file across concurrent queries.
+static TIMINGS_FILE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+fn timings_path() -> std::path::PathBuf {
+    crate::thunderclaude_dir().join("query-timings.json")
+}
+
+fn read_timings() -> Vec<QueryTiming> {
+    std::fs::read_to_string(timings_path())
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Token total from a stream-json `result` message (Claude `usage` or Gemini `stats`).
+fn result_token_count(result: &serde_json::Value) -> Option<u64> {
+    if let Some(total) = result.pointer("/stats/total_tokens").and_then(|v| v.as_u64()) {
+        return Some(total);
+    }
+    let usage = result.get("usage")?;
+    let total = ["input_tokens", "output_tokens", "cache_creation_input_tokens", "cache_read_input_tokens"]
+        .iter()
+        .filter_map(|k| usage.get(k).and_then(|v| v.as_u64()))
+        .sum();
+    Some(total)
+}
+
+fn record_query_timing(timing: QueryTiming) {
+    let _guard = TIMINGS_FILE_LOCK.lock().unwrap();
+    let mut timings = read_timings();
+    timings.push(timing);
+    if timings.len() > MAX_QUERY_TIMINGS {
+        timings.drain(..timings.len() - MAX_QUERY_TIMINGS);
+    }
+    let path = timings_path();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    match serde_json::to_string(&timings) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(&path, json) {
+                eprintln!("Warning: Failed to write query timings: {}", e);
+            }
         }
-        c
+        Err(e) => eprintln!("Warning: Failed to serialize query timings: {}", e),
+    }
+}
+
+/// Currently running queries plus the `limit` most recent completed ones.
+pub fn query_timings(limit: usize) -> QueryTimings {
+    let mut running: Vec<RunningQuery> = RUNNING_QUERIES.lock().unwrap().values().cloned().collect();
+    running.sort_by_key(|r| r.started_at);
+    let completed = {
+        let _guard = TIMINGS_FILE_LOCK.lock().unwrap();
+        read_timings()
     };
+    QueryTimings {
+        running,
+        completed: completed.into_iter().rev().take(limit).collect(),
+    }
+}
+
+/// Messages longer than this (in bytes) are piped via stdin instead of argv
+/// (Windows cmd.exe command lines are limited to ~8191 chars).
+pub const STDIN_PIPE_THRESHOLD: usize = 6000;
+
+fn stdin_threshold(config: &QueryConfig) -> usize {
+    config.stdin_threshold.unwrap_or(STDIN_PIPE_THRESHOLD)
+}
+
+/// Whether a message of `len` bytes goes via stdin for this query.
+fn pipes_stdin(config: &QueryConfig, len: usize) -> bool {
+    config.force_stdin.unwrap_or(false) || len > stdin_threshold(config)
+}
+
+/// Flags `extra_args` may not set, per engine: (flag, takes a value). The wrapper
+/// owns how the prompt is fed (argv vs stdin) and parses stream-json output.
+const RESERVED_FLAGS: &[(&str, &[(&str, bool)])] = &[
+    ("claude", &[("-p", false), ("--print", false), ("--output-format", true), ("--input-format", true)]),
+    ("gemini", &[("-p", true), ("--prompt", true), ("-o", true), ("--output-format", true)]),
+];
+
+/// `extra_args` minus reserved flags (and their values, including `--flag=value`).
+fn filter_extra_args<'a>(engine: &str, args: &'a [String]) -> Vec<&'a String> {
+    let reserved = RESERVED_FLAGS
+        .iter()
+        .find(|(e, _)| *e == engine)
+        .map(|(_, flags)| *flags)
+        .unwrap_or(&[]);
+    let mut kept = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let name = arg.split('=').next().unwrap_or(arg);
+        match reserved.iter().find(|(flag, _)| *flag == name) {
+            Some((flag, takes_value)) => {
+                eprintln!("Warning: Ignoring reserved extra arg {}", flag);
+                if *takes_value && !arg.contains('=') {
+                    iter.next();
+                }
+            }
+            None => kept.push(arg),
+        }
+    }
+    kept
+}
+
+/// A fully configured (not yet spawned) CLI command plus how it will be fed.
+struct PreparedCommand {
+    cmd: Command,
+    binary: String,
+    engine: String,
+    is_gemini: bool,
+    /// Message text to write to stdin (None = it's on the command line)
+    stdin_payload: Option<String>,
+}
+
+/// Gemini has no system-prompt flag: the system prompt (and any appended
+/// prompt) is inlined ahead of the message with section markers.
+fn gemini_prompt(config: &QueryConfig) -> String {
+    let instructions: Vec<&str> = [&config.system_prompt, &config.append_system_prompt]
+        .into_iter()
+        .filter_map(|p| p.as_deref())
+        .collect();
+    if instructions.is_empty() {
+        config.message.clone()
+    } else {
+        format!("[System Instructions]\n{}\n\n[User Message]\n{}", instructions.join("\n\n"), config.message)
+    }
+}
+
+/// The prompt text to write to stdin, when it's too long (or forced) for argv.
+/// Both CLIs read the prompt from piped stdin when it's absent from argv.
+fn stdin_payload(config: &QueryConfig, is_gemini: bool) -> Option<String> {
+    let prompt = if is_gemini { gemini_prompt(config) } else { config.message.clone() };
+    pipes_stdin(config, prompt.len()).then_some(prompt)
+}
+
+/// CLI arguments for a query (after the binary and any pre-args). Pure: no
+/// binary discovery or filesystem access, so flag logic can be checked in isolation.
+fn build_command_args(config: &QueryConfig, is_gemini: bool) -> Vec<String> {
+    let engine = if is_gemini { "gemini" } else { "claude" };
+    let mut args: Vec<String> = Vec::new();
 
     if is_gemini {
         // Gemini CLI: --prompt <message> --output-format stream-json --model <m> --yolo
-        // Prepend system prompt to message if provided
-        let full_message = if let Some(ref sp) = config.system_prompt {
-            format!("[System Instructions]\n{}\n\n[User Message]\n{}", sp, config.message)
-        } else {
-            config.message.clone()
-        };
-
-        cmd.arg("--prompt").arg(&full_message)
-            .arg("--output-format").arg("stream-json")
-            .arg("--yolo");
+        // Long prompts go via stdin: Gemini CLI runs non-interactively on piped
+        // input and treats it as the prompt when --prompt is absent.
+        let prompt = gemini_prompt(config);
+        if !pipes_stdin(config, prompt.len()) {
+            args.extend(["--prompt".to_string(), prompt]);
+        }
+        args.extend(["--output-format", "stream-json", "--yolo"].map(String::from));
 
         if let Some(ref model) = config.model {
-            cmd.arg("--model").arg(model);
+            args.extend(["--model".to_string(), model.clone()]);
         }
         if let Some(ref sid) = config.session_id {
             if config.resume {
-                cmd.arg("--resume").arg(sid);
+                args.extend(["--resume".to_string(), sid.clone()]);
             }
         }
     } else {
         // Claude CLI: -p --verbose --output-format stream-json --model <m> <message>
-        cmd.arg("-p")
-            .arg("--verbose")
-            .arg("--output-format")
-            .arg("stream-json");
+        args.extend(["-p", "--verbose", "--output-format", "stream-json"].map(String::from));
 
         if let Some(ref model) = config.model {
-            cmd.arg("--model").arg(model);
+            args.extend(["--model".to_string(), model.clone()]);
         }
         if let Some(ref mcp) = config.mcp_config {
-            cmd.arg("--mcp-config").arg(mcp);
+            args.extend(["--mcp-config".to_string(), mcp.clone()]);
         }
         if let Some(ref prompt) = config.system_prompt {
-            cmd.arg("--system-prompt").arg(prompt);
+            args.extend(["--system-prompt".to_string(), prompt.clone()]);
+        }
+        if let Some(ref prompt) = config.append_system_prompt {
+            args.extend(["--append-system-prompt".to_string(), prompt.clone()]);
         }
         if let Some(turns) = config.max_turns {
-            cmd.arg("--max-turns").arg(turns.to_string());
+            args.extend(["--max-turns".to_string(), turns.to_string()]);
         }
-        // Tool control: --tools "" disables all built-in tools (Read, Write, Bash, etc.)
-        if let Some(ref tools) = config.tools {
-            cmd.arg("--tools").arg(tools);
+        // Tool control: allow/deny lists win over the legacy --tools string,
+        // where --tools "" disables all built-in tools (Read, Write, Bash, etc.)
+        if config.allowed_tools.is_some() || config.disallowed_tools.is_some() {
+            if let Some(ref allowed) = config.allowed_tools {
+                args.extend(["--allowedTools".to_string(), allowed.join(",")]);
+            }
+            if let Some(ref disallowed) = config.disallowed_tools {
+                args.extend(["--disallowedTools".to_string(), disallowed.join(",")]);
+            }
+        } else if let Some(ref tools) = config.tools {
+            args.extend(["--tools".to_string(), tools.clone()]);
         }
         // Strict MCP: ignore user's default MCP servers, only use explicit --mcp-config
         if config.strict_mcp {
-            cmd.arg("--strict-mcp-config");
+            args.push("--strict-mcp-config".to_string());
         }
         // Permission mode: controls tool approval behavior (default/acceptEdits/bypassPermissions)
         if let Some(ref mode) = config.permission_mode {
-            cmd.arg("--permission-mode").arg(mode);
+            args.extend(["--permission-mode".to_string(), mode.clone()]);
         }
         if let Some(ref sid) = config.session_id {
             if config.resume {
-                cmd.arg("-r").arg(sid);
+                args.extend(["-r".to_string(), sid.clone()]);
             }
         }
+    }
 
-        // Claude: user message goes last as positional arg.
-        // Long messages are piped via stdin instead (Windows cmd.exe limit: ~8191 chars).
-        if config.message.len() <= 6000 {
-            cmd.arg(&config.message);
+    if let Some(ref extra) = config.extra_args {
+        args.extend(filter_extra_args(engine, extra).into_iter().cloned());
+    }
+
+    // Claude: user message goes last as positional arg.
+    // Long messages are piped via stdin instead (Windows cmd.exe limit: ~8191 chars);
+    // `-p` reads from stdin when no positional message arg is provided.
+    if !is_gemini && !pipes_stdin(config, config.message.len()) {
+        args.push(config.message.clone());
+    }
+    args
+}
+
+/// Start killing a CLI process. On Windows the whole tree goes: `.cmd` shims
+/// run the real CLI (node) as a child of cmd.exe, which killing cmd.exe alone
+/// would orphan. Callers still `wait()` to reap the child.
+pub fn kill_process_tree(child: &mut Child) -> std::io::Result<()> {
+    #[cfg(target_os = "windows")]
+    if let Some(pid) = child.id() {
+        use std::os::windows::process::CommandExt;
+        let status = std::process::Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid.to_string()])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .creation_flags(0x08000000) // CREATE_NO_WINDOW
+            .status();
+        if matches!(status, Ok(s) if s.success()) {
+            return Ok(());
         }
     }
+    child.start_kill()
+}
+
+/// Variables removed from every CLI process (they make Claude refuse to run
+/// nested inside another Claude session); `QueryConfig::env` can't set them back.
+const RESERVED_ENV_VARS: &[&str] = &["CLAUDECODE", "CLAUDE_CODE_ENTRY_POINT"];
+
+/// Resolve the binary and build the full command for a query, without spawning.
+fn prepare_command(config: &QueryConfig) -> PreparedCommand {
+    let engine = config.engine.as_deref().unwrap_or("claude");
+    let is_gemini = engine == "gemini";
+
+    let (binary, pre_args) = if is_gemini {
+        find_gemini_binary()
+    } else {
+        (find_claude_binary(), vec![])
+    };
+
+    let is_cmd = binary.ends_with(".cmd");
+    let mut cmd = if is_cmd {
+        let mut c = Command::new("cmd.exe");
+        c.arg("/c").arg(&binary);
+        for arg in &pre_args {
+            c.arg(arg);
+        }
+        c
+    } else {
+        let mut c = Command::new(&binary);
+        for arg in &pre_args {
+            c.arg(arg);
+        }
+        c
+    };
+
+    cmd.args(build_command_args(config, is_gemini));
+    let stdin_payload = stdin_payload(config, is_gemini);
 
     // Set working directory to the active project root (if available)
     if let Some(ref cwd) = config.cwd {
         cmd.current_dir(cwd);
     }
 
-    // For long Claude messages, pipe via stdin instead of command-line args.
-    // Claude CLI `-p` reads from stdin when no positional message arg is provided.
-    let pipe_stdin = !is_gemini && config.message.len() > 6000;
+    // macOS GUI launches lack the login shell's PATH (node for npm-installed CLIs)
+    #[cfg(target_os = "macos")]
+    cmd.env("PATH", effective_path());
 
     // Strip env vars that prevent Claude from running inside another Claude session
-    cmd.env_remove("CLAUDECODE")
-        .env_remove("CLAUDE_CODE_ENTRY_POINT")
-        .stdin(if pipe_stdin { Stdio::piped() } else { Stdio::null() })
+    for var in RESERVED_ENV_VARS {
+        cmd.env_remove(var);
+    }
+    cmd.stdin(if stdin_payload.is_some() { Stdio::piped() } else { Stdio::null() })
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
+    // Per-query variables go last so they override everything above, except
+    // the reserved ones stripped above
+    if let Some(ref env) = config.env {
+        cmd.envs(env.iter().filter(|(key, _)| {
+            !RESERVED_ENV_VARS.iter().any(|reserved| key.eq_ignore_ascii_case(reserved))
+        }));
+    }
+
     // Hide console window on Windows
     #[cfg(target_os = "windows")]
     {
@@ -366,15 +997,216 @@ pub async fn run_query(app: &AppHandle, query_id: &str, config: QueryConfig, reg
     // kill_on_drop ensures child is killed if the future is dropped (e.g. cancel)
     cmd.kill_on_drop(true);
 
+    PreparedCommand {
+        cmd,
+        binary,
+        engine: engine.to_string(),
+        is_gemini,
+        stdin_payload,
+    }
+}
+
+/// How a query would be spawned, for checking stdin-vs-argv routing.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpawnPlan {
+    pub will_pipe_stdin: bool,
+    /// Program + arguments, in bytes (each counted with one separator)
+    pub argv_byte_length: usize,
+    pub message_byte_length: usize,
+    pub stdin_threshold: usize,
+}
+
+/// Build the command for `config` without spawning and report its routing.
+pub fn spawn_plan(config: &QueryConfig) -> SpawnPlan {
+    let prepared = prepare_command(config);
+    let std_cmd = prepared.cmd.as_std();
+    let argv_byte_length = std::iter::once(std_cmd.get_program())
+        .chain(std_cmd.get_args())
+        .map(|a| a.len() + 1)
+        .sum();
+    SpawnPlan {
+        will_pipe_stdin: prepared.stdin_payload.is_some(),
+        argv_byte_length,
+        message_byte_length: config.message.len(),
+        stdin_threshold: stdin_threshold(config),
+    }
+}
+
+// ── Token estimates ──────────────────────────────────────────────────────────
+
+/// Rough token count (~4 characters per token); good enough for budget warnings.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Context window (tokens) by model-name prefix. Unknown Claude models assume
+/// 200k, unknown Gemini models 1M.
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("claude-", 200_000),
+    ("opus", 200_000),
+    ("sonnet", 200_000),
+    ("haiku", 200_000),
+    ("gemini-", 1_048_576),
+];
+
+fn context_window(model: Option<&str>, is_gemini: bool) -> usize {
+    let fallback = if is_gemini { 1_048_576 } else { 200_000 };
+    model
+        .and_then(|m| CONTEXT_WINDOWS.iter().find(|(prefix, _)| m.starts_with(prefix)))
+        .map(|(_, window)| *window)
+        .unwrap_or(fallback)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenSection {
+    pub name: String,
+    pub tokens: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryTokenEstimate {
+    pub sections: Vec<TokenSection>,
+    pub total: usize,
+    pub context_window: usize,
+    pub exceeds_context: bool,
+    pub warning: Option<String>,
+}
+
+/// Estimate the tokens a query would send, per section. `contexts` are named
+/// blocks (memory, vault CLAUDE.md) the frontend folds into the system prompts:
+/// each one found there becomes its own section and is taken out of that
+/// prompt's count. History loaded by `--resume` is not counted.
+pub fn estimate_query_tokens(config: &QueryConfig, contexts: &[(&str, &str)]) -> QueryTokenEstimate {
+    let is_gemini = config.engine.as_deref() == Some("gemini");
+    let mut prompts: Vec<(&str, String)> = [
+        ("systemPrompt", &config.system_prompt),
+        ("appendSystemPrompt", &config.append_system_prompt),
+    ]
+    .into_iter()
+    .filter_map(|(name, prompt)| prompt.clone().map(|p| (name, p)))
+    .collect();
+
+    let mut context_sections = Vec::new();
+    for (name, text) in contexts.iter().filter(|(_, text)| !text.is_empty()) {
+        if let Some((_, prompt)) = prompts.iter_mut().find(|(_, p)| p.contains(text)) {
+            *prompt = prompt.replacen(text, "", 1);
+            context_sections.push(TokenSection { name: name.to_string(), tokens: estimate_tokens(text) });
+        }
+    }
+
+    let mut sections: Vec<TokenSection> = prompts
+        .iter()
+        .map(|(name, prompt)| TokenSection { name: name.to_string(), tokens: estimate_tokens(prompt) })
+        .collect();
+    sections.extend(context_sections);
+    if is_gemini && (config.system_prompt.is_some() || config.append_system_prompt.is_some()) {
+        // Gemini gets the prompts inlined with section markers (see prepare_command)
+        sections.push(TokenSection {
+            name: "wrapper".into(),
+            tokens: estimate_tokens("[System Instructions]\n\n\n[User Message]\n"),
+        });
+    }
+    sections.push(TokenSection { name: "message".into(), tokens: estimate_tokens(&config.message) });
+
+    let total = sections.iter().map(|s| s.tokens).sum();
+    let window = context_window(config.model.as_deref(), is_gemini);
+    let exceeds_context = total > window;
+    let warning = if exceeds_context {
+        Some(format!("Estimated {} tokens exceeds the {} token context window", total, window))
+    } else if config.resume && total * 2 > window {
+        Some("Over half the context window before resumed history is added".to_string())
+    } else {
+        None
+    };
+
+    QueryTokenEstimate { sections, total, context_window: window, exceeds_context, warning }
+}
+
+/// Program and arguments as one shell-like line (arguments with spaces or
+/// quotes are double-quoted). For display only, not for re-execution.
+fn command_line(cmd: &Command) -> String {
+    let std_cmd = cmd.as_std();
+    std::iter::once(std_cmd.get_program())
+        .chain(std_cmd.get_args())
+        .map(|a| {
+            let a = a.to_string_lossy();
+            if a.is_empty() || a.contains([' ', '"', '\t', '\n']) {
+                format!("\"{}\"", a.replace('"', "\\\""))
+            } else {
+                a.into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `claude-done` exit code for a query killed by its `timeout_secs`.
+pub const TIMEOUT_EXIT_CODE: i32 = -2;
+
+/// Run a query using either Claude or Gemini CLI and stream output as events
+/// Returns the CLI session id, or the command line for a `dry_run`.
+pub async fn run_query(app: &AppHandle, query_id: &str, config: QueryConfig, registry: ProcessRegistry) -> Result<String, String> {
+    let PreparedCommand { mut cmd, binary, engine, is_gemini, stdin_payload } = prepare_command(&config);
+
+    let started_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let started = std::time::Instant::now();
+
+    let events = if config.background.unwrap_or(false) { "claude-background" } else { "claude" };
+    let (message_event, raw_event, error_event, done_event) = (
+        format!("{}-message", events),
+        format!("{}-raw", events),
+        format!("{}-error", events),
+        format!("{}-done", events),
+    );
+
+    if config.dry_run.unwrap_or(false) {
+        let command = command_line(&cmd);
+        let _ = app.emit(
+            &done_event,
+            serde_json::json!({
+                "queryId": query_id,
+                "exitCode": 0,
+                "sessionId": null,
+                "diagnosis": null,
+                "command": command,
+                "stdinBytes": stdin_payload.as_ref().map(|p| p.len()),
+            }),
+        );
+        return Ok(command);
+    }
+
+    if config.debug.unwrap_or(false) {
+        let std_cmd = cmd.as_std();
+        let _ = app.emit(
+            &format!("{}-spawn", events),
+            serde_json::json!({
+                "queryId": query_id,
+                "engine": engine,
+                "binary": binary,
+                "program": std_cmd.get_program().to_string_lossy(),
+                "args": std_cmd.get_args().map(|a| a.to_string_lossy()).collect::<Vec<_>>(),
+                "cwd": std_cmd.get_current_dir().map(|d| d.to_string_lossy()),
+                // A piped message isn't in args; report only its size
+                "stdinBytes": stdin_payload.as_ref().map(|p| p.len()),
+            }),
+        );
+    }
+
     let mut child = cmd
         .spawn()
         .map_err(|e| format!("Failed to spawn {}: {} (binary: {})", engine, e, binary))?;
 
-    // Pipe long messages via stdin (Claude CLI reads from stdin in -p mode when no positional arg)
-    if pipe_stdin {
+    // Pipe long messages via stdin (both CLIs read the prompt from stdin when it's not in argv)
+    if let Some(payload) = stdin_payload {
         if let Some(mut stdin_handle) = child.stdin.take() {
             use tokio::io::AsyncWriteExt;
-            let msg_bytes = config.message.as_bytes().to_vec();
+            let msg_bytes = payload.into_bytes();
             tokio::spawn(async move {
                 let _ = stdin_handle.write_all(&msg_bytes).await;
                 // Drop closes stdin → EOF → CLI processes the message
@@ -387,59 +1219,112 @@ pub async fn run_query(app: &AppHandle, query_id: &str, config: QueryConfig, reg
 
     // Register the process so it can be cancelled via cancel_query
     registry.lock().await.insert(query_id.to_string(), child);
+    let running = RunningQueryGuard::register(RunningQuery {
+        query_id: query_id.to_string(),
+        engine: engine.clone(),
+        model: config.model.clone(),
+        started_at,
+    });
 
     let query_id_owned = query_id.to_string();
-    let engine_name = engine.to_string();
+    let engine_name = engine.clone();
     let app_stdout = app.clone();
 
     // Stream stdout → events
-    let stdout_handle = tokio::spawn({
+    let mut stdout_handle = tokio::spawn({
         let qid = query_id_owned.clone();
         let eng = engine_name.clone();
         async move {
             let reader = BufReader::new(stdout);
             let mut lines = reader.lines();
             let mut last_session_id: Option<String> = None;
+            let mut token_count: Option<u64> = None;
 
             while let Ok(Some(line)) = lines.next_line().await {
                 if line.trim().is_empty() {
                     continue;
                 }
+                // Non-JSON output (CLI warnings, ANSI noise) goes to its own event
+                let Ok(val) = serde_json::from_str::<serde_json::Value>(&line) else {
+                    let _ = app_stdout.emit(
+                        &raw_event,
+                        serde_json::json!({ "queryId": qid, "data": line, "engine": eng }),
+                    );
+                    continue;
+                };
                 // Try to extract session_id from any JSON message
-                if let Ok(val) = serde_json::from_str::<serde_json::Value>(&line) {
-                    if let Some(sid) = val.get("session_id").and_then(|v| v.as_str()) {
-                        if !sid.is_empty() {
-                            last_session_id = Some(sid.to_string());
-                        }
+                if let Some(sid) = val.get("session_id").and_then(|v| v.as_str()) {
+                    if !sid.is_empty() {
+                        last_session_id = Some(sid.to_string());
                     }
                 }
+                if val.get("type").and_then(|v| v.as_str()) == Some("result") {
+                    token_count = result_token_count(&val).or(token_count);
+                }
                 let _ = app_stdout.emit(
-                    "claude-message",
+                    &message_event,
                     serde_json::json!({ "queryId": qid, "data": line, "engine": eng }),
                 );
             }
-            last_session_id
+            (last_session_id, token_count)
         }
     });
 
-    // Stream stderr → events
+    // Stream stderr → events (keeping a tail for failure diagnosis)
     let app_stderr = app.clone();
     let qid_err = query_id_owned.clone();
-    tokio::spawn(async move {
-        let reader = BufReader::new(stderr);
-        let mut lines = reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            if !line.trim().is_empty() {
-                let _ = app_stderr.emit(
-                    "claude-error",
-                    serde_json::json!({ "queryId": qid_err, "data": line }),
-                );
+    let stderr_tail: Arc<std::sync::Mutex<Vec<String>>> = Arc::default();
+    let stderr_handle = tokio::spawn({
+        let tail = stderr_tail.clone();
+        let error_event = error_event.clone();
+        async move {
+            let reader = BufReader::new(stderr);
+            let mut lines = reader.lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if !line.trim().is_empty() {
+                    {
+                        let mut tail = tail.lock().unwrap();
+                        if tail.len() == STDERR_TAIL_LINES {
+                            tail.remove(0);
+                        }
+                        tail.push(line.clone());
+                    }
+                    let _ = app_stderr.emit(
+                        &error_event,
+                        serde_json::json!({ "queryId": qid_err, "data": line }),
+                    );
+                }
             }
         }
     });
 
     // Wait for stdout/stderr streams to finish (process exit closes the pipes)
-    let session_id = stdout_handle.await.unwrap_or(None);
+    let mut timed_out = false;
+    let (session_id, token_count) = match config.timeout_secs.filter(|&s| s > 0) {
+        Some(secs) => {
+            match tokio::time::timeout(std::time::Duration::from_secs(secs), &mut stdout_handle).await {
+                Ok(result) => result.unwrap_or((None, None)),
+                Err(_) => {
+                    // Stuck CLI: kill it and stop reading
+                    timed_out = true;
+                    if let Some(mut child) = registry.lock().await.remove(&query_id_owned) {
+                        let _ = kill_process_tree(&mut child);
+                        let _ = child.wait().await;
+                    }
+                    stdout_handle.abort();
+                    let _ = app.emit(
+                        &error_event,
+                        serde_json::json!({
+                            "queryId": query_id_owned,
+                            "data": format!("Query timed out after {}s", secs),
+                        }),
+                    );
+                    (None, None)
+                }
+            }
+        }
+        None => stdout_handle.await.unwrap_or((None, None)),
+    };
 
     // Retrieve the child from registry and wait for it (may already be exited)
     let status = {
@@ -456,21 +1341,324 @@ pub async fn run_query(app: &AppHandle, query_id: &str, config: QueryConfig, reg
 
     // Gemini CLI has a known libuv assertion crash on Windows that causes non-zero
     // exit even when output is complete. Treat it as success if we got a session_id.
-    let exit_code = if is_gemini && raw_exit != 0 && session_id.is_some() {
+    let exit_code = if timed_out {
+        TIMEOUT_EXIT_CODE
+    } else if is_gemini && raw_exit != 0 && session_id.is_some() {
         0 // Output was received successfully despite process crash
     } else {
         raw_exit
     };
 
+    drop(running);
+    record_query_timing(QueryTiming {
+        query_id: query_id_owned.clone(),
+        engine: engine_name,
+        model: config.model.clone(),
+        started_at,
+        duration_ms: started.elapsed().as_millis() as u64,
+        exit_code,
+        token_count,
+    });
+
+    // On failure, summarize stderr (give its reader a moment to drain first)
+    let diagnosis = if exit_code != 0 {
+        let _ = tokio::time::timeout(std::time::Duration::from_millis(500), stderr_handle).await;
+        let tail = stderr_tail.lock().unwrap().clone();
+        Some(diagnose_failure(&tail))
+    } else {
+        None
+    };
+
     // Emit completion event
     let _ = app.emit(
-        "claude-done",
+        &done_event,
         serde_json::json!({
             "queryId": query_id_owned,
             "exitCode": exit_code,
             "sessionId": session_id,
+            "diagnosis": diagnosis,
         }),
     );
 
     Ok(session_id.unwrap_or_default())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    fn category(text: &str) -> String {
+        diagnose_failure(&lines(text)).category
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn config(message: &str) -> QueryConfig {
+        QueryConfig { message: message.to_string(), ..Default::default() }
+    }
+
+    // ── build_command_args ──
+
+    #[test]
+    fn claude_minimal_args() {
+        assert_eq!(
+            build_command_args(&config("hi"), false),
+            strings(&["-p", "--verbose", "--output-format", "stream-json", "hi"])
+        );
+    }
+
+    #[test]
+    fn claude_flags_in_order() {
+        let config = QueryConfig {
+            model: Some("claude-sonnet-4-5".into()),
+            mcp_config: Some("/tmp/mcp.json".into()),
+            system_prompt: Some("Be brief.".into()),
+            append_system_prompt: Some("Use metric units.".into()),
+            max_turns: Some(3),
+            strict_mcp: true,
+            permission_mode: Some("acceptEdits".into()),
+            session_id: Some("abc-123".into()),
+            resume: true,
+            ..config("hi")
+        };
+        assert_eq!(
+            build_command_args(&config, false),
+            strings(&[
+                "-p", "--verbose", "--output-format", "stream-json",
+                "--model", "claude-sonnet-4-5",
+                "--mcp-config", "/tmp/mcp.json",
+                "--system-prompt", "Be brief.",
+                "--append-system-prompt", "Use metric units.",
+                "--max-turns", "3",
+                "--strict-mcp-config",
+                "--permission-mode", "acceptEdits",
+                "-r", "abc-123",
+                "hi",
+            ])
+        );
+    }
+
+    #[test]
+    fn claude_session_id_without_resume_starts_fresh() {
+        let config = QueryConfig { session_id: Some("abc-123".into()), ..config("hi") };
+        assert!(!build_command_args(&config, false).contains(&"-r".to_string()));
+    }
+
+    #[test]
+    fn claude_allow_and_deny_lists_replace_legacy_tools() {
+        let config = QueryConfig {
+            tools: Some("Bash".into()),
+            allowed_tools: Some(vec!["Read".into(), "Grep".into()]),
+            disallowed_tools: Some(vec!["Bash".into()]),
+            ..config("hi")
+        };
+        assert_eq!(
+            build_command_args(&config, false),
+            strings(&[
+                "-p", "--verbose", "--output-format", "stream-json",
+                "--allowedTools", "Read,Grep",
+                "--disallowedTools", "Bash",
+                "hi",
+            ])
+        );
+    }
+
+    #[test]
+    fn claude_legacy_tools_empty_disables_all() {
+        let config = QueryConfig { tools: Some(String::new()), ..config("hi") };
+        assert_eq!(
+            build_command_args(&config, false),
+            strings(&["-p", "--verbose", "--output-format", "stream-json", "--tools", "", "hi"])
+        );
+    }
+
+    #[test]
+    fn reserved_extra_args_are_dropped() {
+        let config = QueryConfig {
+            extra_args: Some(strings(&["--output-format", "json", "--add-dir", "/src", "--print", "--input-format=text"])),
+            ..config("hi")
+        };
+        assert_eq!(
+            build_command_args(&config, false),
+            strings(&["-p", "--verbose", "--output-format", "stream-json", "--add-dir", "/src", "hi"])
+        );
+    }
+
+    #[test]
+    fn gemini_inlines_system_prompts() {
+        let config = QueryConfig {
+            model: Some("gemini-2.5-pro".into()),
+            system_prompt: Some("Be brief.".into()),
+            append_system_prompt: Some("Use metric units.".into()),
+            mcp_config: Some("/tmp/mcp.json".into()),
+            permission_mode: Some("plan".into()),
+            session_id: Some("g-1".into()),
+            resume: true,
+            ..config("hi")
+        };
+        assert_eq!(
+            build_command_args(&config, true),
+            strings(&[
+                "--prompt", "[System Instructions]\nBe brief.\n\nUse metric units.\n\n[User Message]\nhi",
+                "--output-format", "stream-json", "--yolo",
+                "--model", "gemini-2.5-pro",
+                "--resume", "g-1",
+            ])
+        );
+    }
+
+    #[test]
+    fn gemini_without_system_prompt_sends_message_as_is() {
+        assert_eq!(
+            build_command_args(&config("hi"), true),
+            strings(&["--prompt", "hi", "--output-format", "stream-json", "--yolo"])
+        );
+    }
+
+    #[test]
+    fn message_over_stdin_threshold_leaves_argv() {
+        let at_threshold = QueryConfig { stdin_threshold: Some(5), ..config("hello") };
+        assert_eq!(build_command_args(&at_threshold, false).last().unwrap(), "hello");
+        assert_eq!(stdin_payload(&at_threshold, false), None);
+
+        let over = QueryConfig { stdin_threshold: Some(5), ..config("hello!") };
+        assert_eq!(
+            build_command_args(&over, false),
+            strings(&["-p", "--verbose", "--output-format", "stream-json"])
+        );
+        assert_eq!(stdin_payload(&over, false).as_deref(), Some("hello!"));
+    }
+
+    #[test]
+    fn default_stdin_threshold_applies() {
+        let long = "x".repeat(STDIN_PIPE_THRESHOLD + 1);
+        assert!(!build_command_args(&config(&long), false).contains(&long));
+        assert_eq!(stdin_payload(&config(&long), false), Some(long));
+
+        let short = "x".repeat(STDIN_PIPE_THRESHOLD);
+        assert!(build_command_args(&config(&short), false).contains(&short));
+    }
+
+    #[test]
+    fn force_stdin_pipes_short_messages() {
+        let config = QueryConfig { force_stdin: Some(true), ..config("hi") };
+        assert!(!build_command_args(&config, false).contains(&"hi".to_string()));
+        assert_eq!(stdin_payload(&config, false).as_deref(), Some("hi"));
+    }
+
+    #[test]
+    fn gemini_threshold_counts_the_inlined_prompt() {
+        let config = QueryConfig {
+            system_prompt: Some("Be brief.".into()),
+            stdin_threshold: Some(20),
+            ..config("hi")
+        };
+        assert_eq!(
+            build_command_args(&config, true),
+            strings(&["--output-format", "stream-json", "--yolo"])
+        );
+        assert_eq!(
+            stdin_payload(&config, true).as_deref(),
+            Some("[System Instructions]\nBe brief.\n\n[User Message]\nhi")
+        );
+    }
+
+    // ── diagnose_failure ──
+
+    #[test]
+    fn diagnoses_auth_errors() {
+        assert_eq!(
+            category(r#"API Error: 401 {"type":"error","error":{"type":"authentication_error","message":"OAuth token has expired."}}"#),
+            "auth"
+        );
+        assert_eq!(category("Invalid API key · Please run /login"), "auth");
+        assert_eq!(category(r#"[API Error: {"error":{"code": 401,"status":"UNAUTHENTICATED"}}]"#), "auth");
+    }
+
+    #[test]
+    fn diagnoses_rate_limits() {
+        assert_eq!(
+            category(r#"API Error: 429 {"type":"error","error":{"type":"rate_limit_error"}}"#),
+            "rate_limit"
+        );
+        assert_eq!(category(r#"API Error: 529 {"type":"error","error":{"type":"overloaded_error"}}"#), "rate_limit");
+        assert_eq!(category("Claude AI usage limit reached|1760000000"), "rate_limit");
+        assert_eq!(category("Error: status 429 RESOURCE_EXHAUSTED"), "rate_limit");
+    }
+
+    #[test]
+    fn diagnoses_model_not_found() {
+        assert_eq!(
+            category(r#"API Error: 404 {"type":"error","error":{"type":"not_found_error","message":"model: claude-x"}}"#),
+            "model_not_found"
+        );
+    }
+
+    #[test]
+    fn diagnoses_mcp_failures() {
+        assert_eq!(category("MCP server \"github\" failed to start"), "mcp");
+    }
+
+    #[test]
+    fn diagnoses_network_errors() {
+        assert_eq!(category("Error: connect ECONNREFUSED 127.0.0.1:443"), "network");
+        assert_eq!(category("TypeError: fetch failed"), "network");
+    }
+
+    #[test]
+    fn diagnoses_trust_prompt() {
+        assert_eq!(category("Do you trust the files in this folder?"), "trust");
+    }
+
+    #[test]
+    fn diagnoses_missing_cli() {
+        assert_eq!(category("'claude' is not recognized as an internal or external command,"), "not_installed");
+        assert_eq!(category("env: node: No such file or directory"), "not_installed");
+        assert_eq!(category("zsh: command not found: gemini"), "not_installed");
+    }
+
+    #[test]
+    fn ignores_bare_codes_and_file_errors() {
+        assert_eq!(category("Read 401 lines from src/main.rs"), "unknown");
+        assert_eq!(category("Processed 429 files"), "unknown");
+        assert_eq!(category("cat: notes.md: No such file or directory"), "unknown");
+        assert_eq!(category("Error: ENOENT: no such file or directory, open 'config.json'"), "unknown");
+    }
+
+    #[test]
+    fn keeps_last_eight_lines_as_tail() {
+        let input: Vec<String> = (1..=10).map(|i| format!("line {}", i)).collect();
+        let diagnosis = diagnose_failure(&input);
+        assert_eq!(diagnosis.tail, (3..=10).map(|i| format!("line {}", i)).collect::<Vec<_>>().join("\n"));
+    }
+
+    // ── estimate_query_tokens ──
+
+    #[test]
+    fn context_blocks_are_split_out_of_the_system_prompt() {
+        let memory = "m".repeat(400);
+        let vault = "v".repeat(800);
+        let config = QueryConfig {
+            system_prompt: Some(format!("{}\n{}\n{}", "s".repeat(40), memory, vault)),
+            ..config(&"q".repeat(8))
+        };
+        let estimate = estimate_query_tokens(&config, &[("memory", &memory), ("vaultContext", &vault)]);
+        let sections: Vec<(&str, usize)> = estimate.sections.iter().map(|s| (s.name.as_str(), s.tokens)).collect();
+        assert_eq!(sections, [("systemPrompt", 11), ("memory", 100), ("vaultContext", 200), ("message", 2)]);
+        assert_eq!(estimate.total, 313);
+    }
+
+    #[test]
+    fn context_blocks_missing_from_the_prompts_are_not_counted() {
+        let config = QueryConfig { system_prompt: Some("Be brief.".into()), ..config("hi") };
+        let estimate = estimate_query_tokens(&config, &[("memory", "not in the prompt"), ("vaultContext", "")]);
+        let names: Vec<&str> = estimate.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["systemPrompt", "message"]);
+    }
+}
@@ -5,7 +5,7 @@ use claude::{ProcessRegistry, QueryConfig};
 use std::path::PathBuf;
 use std::sync::Mutex;
 use tauri::{
-    Emitter, Manager,
+    Emitter, Listener, Manager,
     menu::{Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
 };
@@ -24,12 +24,19 @@ struct ProjectConfig {
     enabled_skill_ids: Vec<String>,
     #[serde(default)]
     default_model: Option<String>,
+    /// "claude" or "gemini"; used when a query doesn't name an engine
+    #[serde(default)]
+    default_engine: Option<String>,
+    /// Extra directory names skipped by file search; a `!name` entry re-includes a default
+    #[serde(default)]
+    search_ignored_dirs: Vec<String>,
     created_at: String,
     last_used_at: String,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 struct Settings {
+    #[serde(default = "default_close_to_tray")]
     close_to_tray: bool,
     #[serde(default)]
     vault_path: Option<String>,
@@ -37,34 +44,209 @@ struct Settings {
     projects: Vec<ProjectConfig>,
     #[serde(default)]
     active_project_id: Option<String>,
+    /// Initialize the embedding model during startup warm-up when a vault is configured
+    #[serde(default)]
+    auto_index: bool,
+    /// Texts per embedding call (and per embedding-progress event); unset uses 256
+    #[serde(default)]
+    embedding_batch_size: Option<usize>,
+    /// Threads used to tokenize texts before embedding; unset uses all cores.
+    /// Model inference always uses every core — fastembed doesn't expose the
+    /// ONNX Runtime thread count.
+    #[serde(default, alias = "embedding_threads")]
+    embedding_tokenizer_threads: Option<usize>,
+    /// Minutes between background vault reindex passes; unset disables the scheduler
+    #[serde(default)]
+    auto_reindex_interval_mins: Option<u64>,
+    /// Claude CLI to run instead of the auto-discovered one
+    #[serde(default)]
+    claude_binary_path: Option<String>,
+    /// Gemini CLI (executable or `.js` entry script) to run instead of the auto-discovered one
+    #[serde(default)]
+    gemini_binary_path: Option<String>,
+    /// CLI processes allowed to run at once; further queries wait in a queue (unset uses 3)
+    #[serde(default)]
+    max_concurrent_queries: Option<usize>,
+    /// Largest file read_file_content will return whole; unset uses 1MB
+    #[serde(default)]
+    max_file_read_bytes: Option<u64>,
+    /// Memory notes and sessions go to the OS trash instead of being deleted
+    #[serde(default)]
+    soft_delete: bool,
+    /// File tree commands only touch the active project, the vault and ~/.thunderclaude
+    #[serde(default = "default_restrict_filesystem")]
+    restrict_filesystem: bool,
+    /// Largest image save_temp_image accepts; unset uses 20MB
+    #[serde(default)]
+    max_temp_image_bytes: Option<u64>,
+    /// Temp image folder budget; oldest images are evicted past it (unset uses 200MB)
+    #[serde(default)]
+    max_temp_images_total_bytes: Option<u64>,
+}
+
+/// Off on Linux, where tray support varies by desktop environment and a hidden
+/// window with no tray icon looks like the app vanished.
+fn default_close_to_tray() -> bool {
+    !cfg!(target_os = "linux")
+}
+
+fn default_restrict_filesystem() -> bool {
+    true
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
-            close_to_tray: true,
+            close_to_tray: default_close_to_tray(),
             vault_path: None,
             projects: Vec::new(),
             active_project_id: None,
+            auto_index: false,
+            embedding_batch_size: None,
+            embedding_tokenizer_threads: None,
+            auto_reindex_interval_mins: None,
+            claude_binary_path: None,
+            gemini_binary_path: None,
+            max_concurrent_queries: None,
+            max_file_read_bytes: None,
+            soft_delete: false,
+            restrict_filesystem: default_restrict_filesystem(),
+            max_temp_image_bytes: None,
+            max_temp_images_total_bytes: None,
         }
     }
 }
 
 struct AppState {
     close_to_tray: Mutex<bool>,
+    /// Set once the tray icon is built; closing to tray without one would strand the app
+    tray_available: Mutex<bool>,
     vault_path: Mutex<Option<String>>,
+    auto_index: Mutex<bool>,
+    embedding_batch_size: Mutex<Option<usize>>,
+    embedding_tokenizer_threads: Mutex<Option<usize>>,
+    auto_reindex_interval_mins: Mutex<Option<u64>>,
+    claude_binary_path: Mutex<Option<String>>,
+    gemini_binary_path: Mutex<Option<String>>,
+    /// Running background reindex scheduler, if enabled
+    auto_reindex_task: Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
     projects: Mutex<Vec<ProjectConfig>>,
     active_project_id: Mutex<Option<String>>,
     active_project_root: Mutex<Option<String>>,
     processes: ProcessRegistry,
+    max_concurrent_queries: Mutex<Option<usize>>,
+    max_file_read_bytes: Mutex<Option<u64>>,
+    soft_delete: Mutex<bool>,
+    restrict_filesystem: Mutex<bool>,
+    max_temp_image_bytes: Mutex<Option<u64>>,
+    max_temp_images_total_bytes: Mutex<Option<u64>>,
+    /// Slots for running queries (replaced when the limit changes; holders keep the old one)
+    query_slots: Mutex<std::sync::Arc<tokio::sync::Semaphore>>,
+    /// Queries waiting for a slot; sending on the channel cancels the wait
+    queued_queries: QueuedQueries,
+    /// Proposed file edits awaiting review, keyed by (query_id, path)
+    pending_edits: Mutex<std::collections::HashMap<(String, String), String>>,
 }
 
-fn thunderclaude_dir() -> PathBuf {
+type QueuedQueries = std::sync::Arc<Mutex<std::collections::HashMap<String, tokio::sync::oneshot::Sender<()>>>>;
+
+const DEFAULT_MAX_CONCURRENT_QUERIES: usize = 3;
+
+fn query_semaphore(limit: Option<usize>) -> std::sync::Arc<tokio::sync::Semaphore> {
+    let permits = limit.filter(|&n| n > 0).unwrap_or(DEFAULT_MAX_CONCURRENT_QUERIES);
+    std::sync::Arc::new(tokio::sync::Semaphore::new(permits))
+}
+
+/// Overrides the data directory (takes precedence over `relocate_data_dir`).
+const DATA_DIR_ENV: &str = "THUNDERCLAUDE_DATA_DIR";
+/// File in the default ~/.thunderclaude recording where data was relocated to.
+const DATA_LOCATION_FILE: &str = "data-location";
+
+/// Resolved data directory (filled on first use, updated by `relocate_data_dir`).
+static DATA_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+fn home_dir() -> PathBuf {
     // USERPROFILE on Windows, HOME on Mac/Linux
     let home = std::env::var("USERPROFILE")
         .or_else(|_| std::env::var("HOME"))
         .unwrap_or_default();
-    PathBuf::from(home).join(".thunderclaude")
+    PathBuf::from(home)
+}
+
+fn default_thunderclaude_dir() -> PathBuf {
+    home_dir().join(".thunderclaude")
+}
+
+/// Expand a user-typed path: leading `~`, `$VAR` / `${VAR}` / `%VAR%`
+/// (unset variables are left as typed), and relative paths against the
+/// working directory. Blank input stays blank.
+fn expand_path(raw: &str) -> String {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(i) = rest.find(['$', '%']) {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (name, consumed) = if rest[i..].starts_with('%') {
+            match after.find('%') {
+                Some(end) => (&after[..end], end + 2),
+                None => ("", 1),
+            }
+        } else if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 3),
+                None => ("", 1),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end + 1)
+        };
+        match std::env::var(name).ok().filter(|_| !name.is_empty()) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[i..i + consumed]),
+        }
+        rest = &rest[i + consumed..];
+    }
+    out.push_str(rest);
+
+    if out == "~" {
+        out = home_dir().to_string_lossy().to_string();
+    } else if let Some(tail) = out.strip_prefix("~/").or_else(|| out.strip_prefix("~\\")) {
+        out = home_dir().join(tail).to_string_lossy().to_string();
+    }
+
+    let path = PathBuf::from(&out);
+    if path.is_relative() {
+        if let Ok(cwd) = std::env::current_dir() {
+            return cwd.join(path).to_string_lossy().to_string();
+        }
+    }
+    out
+}
+
+/// App data directory: $THUNDERCLAUDE_DATA_DIR, else the location recorded by
+/// `relocate_data_dir`, else ~/.thunderclaude.
+fn thunderclaude_dir() -> PathBuf {
+    DATA_DIR.lock().unwrap().get_or_insert_with(|| {
+        if let Ok(dir) = std::env::var(DATA_DIR_ENV) {
+            if !dir.trim().is_empty() {
+                return PathBuf::from(dir.trim());
+            }
+        }
+        let default = default_thunderclaude_dir();
+        std::fs::read_to_string(default.join(DATA_LOCATION_FILE))
+            .ok()
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or(default)
+    }).clone()
 }
 
 fn settings_path() -> PathBuf {
@@ -85,6 +267,28 @@ fn resolve_memory_dir(vault_path: &Option<String>) -> PathBuf {
     }
 }
 
+/// Join a frontend-supplied relative path onto `base`, refusing anything that
+/// could land outside it: absolute paths, `..` components, and (for the part
+/// that already exists) symlinks resolving elsewhere.
+fn join_within(base: &std::path::Path, rel: &str) -> Result<PathBuf, String> {
+    use std::path::Component;
+    let rel_path = std::path::Path::new(rel);
+    if rel_path.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(format!("Path escapes its base directory: {}", rel));
+    }
+    let path = base.join(rel_path);
+    if let Ok(base_real) = std::fs::canonicalize(base) {
+        if let Some(existing) = path.ancestors().find(|p| p.exists()) {
+            let real = std::fs::canonicalize(existing)
+                .map_err(|e| format!("Failed to resolve path {}: {}", rel, e))?;
+            if !real.starts_with(&base_real) {
+                return Err(format!("Path escapes its base directory: {}", rel));
+            }
+        }
+    }
+    Ok(path)
+}
+
 fn load_settings_from_disk() -> Settings {
     let path = settings_path();
     if path.exists() {
@@ -97,6 +301,28 @@ fn load_settings_from_disk() -> Settings {
     Settings::default()
 }
 
+/// Snapshot the in-memory settings (what get_settings returns and what gets persisted).
+fn current_settings(state: &AppState) -> Settings {
+    Settings {
+        close_to_tray: *state.close_to_tray.lock().unwrap(),
+        vault_path: state.vault_path.lock().unwrap().clone(),
+        projects: state.projects.lock().unwrap().clone(),
+        active_project_id: state.active_project_id.lock().unwrap().clone(),
+        auto_index: *state.auto_index.lock().unwrap(),
+        embedding_batch_size: *state.embedding_batch_size.lock().unwrap(),
+        embedding_tokenizer_threads: *state.embedding_tokenizer_threads.lock().unwrap(),
+        auto_reindex_interval_mins: *state.auto_reindex_interval_mins.lock().unwrap(),
+        claude_binary_path: state.claude_binary_path.lock().unwrap().clone(),
+        gemini_binary_path: state.gemini_binary_path.lock().unwrap().clone(),
+        max_concurrent_queries: *state.max_concurrent_queries.lock().unwrap(),
+        max_file_read_bytes: *state.max_file_read_bytes.lock().unwrap(),
+        soft_delete: *state.soft_delete.lock().unwrap(),
+        restrict_filesystem: *state.restrict_filesystem.lock().unwrap(),
+        max_temp_image_bytes: *state.max_temp_image_bytes.lock().unwrap(),
+        max_temp_images_total_bytes: *state.max_temp_images_total_bytes.lock().unwrap(),
+    }
+}
+
 fn save_settings_to_disk(settings: &Settings) -> Result<(), String> {
     let dir = thunderclaude_dir();
     std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create dir: {}", e))?;
@@ -116,6 +342,12 @@ async fn send_query(
     let qid = query_id.clone();
     let registry = state.processes.clone();
 
+    if let Some(local_id) = &config.local_session_id {
+        if session_is_readonly(local_id) {
+            return Err("This session is read-only and can't be continued".to_string());
+        }
+    }
+
     // Inject active project root as working directory (if not already set)
     let mut config = config;
     if config.cwd.is_none() {
@@ -124,7 +356,38 @@ async fn send_query(
         }
     }
 
+    // Fill the model/engine/MCP servers the query left unset from the active project
+    if let Some(project) = active_project(&state) {
+        // Strict queries (title generation, commander workers) choose their own
+        // servers, and Gemini has no --mcp-config; a broken global config
+        // shouldn't block the query either
+        let gemini = config.engine.as_deref().or(project.default_engine.as_deref()) == Some("gemini");
+        if config.mcp_config.is_none() && !config.strict_mcp && !gemini {
+            config.mcp_config = project_mcp_config(&project).unwrap_or_else(|e| {
+                eprintln!("Warning: Skipping project MCP servers: {}", e);
+                None
+            });
+        }
+        // The default model belongs to the project's default engine; a query that
+        // picks the other engine keeps that CLI's own default model
+        let project_engine = project.default_engine.as_deref().unwrap_or("claude");
+        let engine = config.engine.as_deref().unwrap_or(project_engine);
+        if config.model.is_none() && engine == project_engine {
+            config.model = project.default_model;
+        }
+        if config.engine.is_none() {
+            config.engine = project.default_engine;
+        }
+    }
+    config.validate()?;
+
+    let slots = state.query_slots.lock().unwrap().clone();
+    let queued = state.queued_queries.clone();
     tokio::spawn(async move {
+        let Some(_permit) = acquire_query_slot(&app, slots, queued, &qid).await else {
+            return;
+        };
+
         if let Err(e) = claude::run_query(&app, &qid, config, registry).await {
             eprintln!("Query error: {}", e);
             let _ = app.emit(
@@ -136,20 +399,121 @@ async fn send_query(
     Ok(query_id)
 }
 
+/// Wait for a free slot when max_concurrent_queries are already running. While
+/// waiting, the query is listed in `queued` (and `claude-queued` is emitted) so
+/// `cancel_query` can drop it; returns None if it did.
+async fn acquire_query_slot(
+    app: &tauri::AppHandle,
+    slots: std::sync::Arc<tokio::sync::Semaphore>,
+    queued: QueuedQueries,
+    query_id: &str,
+) -> Option<tokio::sync::OwnedSemaphorePermit> {
+    if let Ok(permit) = slots.clone().try_acquire_owned() {
+        return Some(permit);
+    }
+    let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel();
+    queued.lock().unwrap().insert(query_id.to_string(), cancel_tx);
+    let _ = app.emit("claude-queued", serde_json::json!({ "queryId": query_id }));
+    tokio::select! {
+        permit = slots.acquire_owned() => {
+            // A missing entry means cancel_query got there first
+            let still_queued = queued.lock().unwrap().remove(query_id).is_some();
+            permit.ok().filter(|_| still_queued)
+        }
+        // Cancelled while queued (cancel_query removed the entry)
+        _ = cancel_rx => None,
+    }
+}
+
+/// Outcome of `cancel_query`.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CancelResult {
+    /// A queued or running query with this id existed
+    cancelled: bool,
+    /// It was still waiting for a slot (no process existed yet)
+    was_queued: bool,
+    /// A CLI process was killed
+    killed: bool,
+    pid: Option<u32>,
+}
+
+/// Cancel a queued or running query and emit `claude-cancelled`. A running
+/// query still gets its `claude-done` from `run_query`; a queued one never
+/// started, so its `claude-done` (exit code -1) is emitted here.
 #[tauri::command]
 async fn cancel_query(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     query_id: String,
-) -> Result<bool, String> {
-    let mut reg = state.processes.lock().await;
-    if let Some(mut child) = reg.remove(&query_id) {
-        let _ = child.kill().await;
-        Ok(true)
-    } else {
-        Ok(false)
+) -> Result<CancelResult, String> {
+    // Still waiting for a slot: drop it from the queue before it spawns
+    if let Some(cancel_tx) = state.queued_queries.lock().unwrap().remove(&query_id) {
+        let _ = cancel_tx.send(());
+        let _ = app.emit("claude-cancelled", serde_json::json!({ "queryId": query_id }));
+        let _ = app.emit(
+            "claude-done",
+            serde_json::json!({ "queryId": query_id, "exitCode": -1, "sessionId": null, "diagnosis": null }),
+        );
+        return Ok(CancelResult { cancelled: true, was_queued: true, killed: false, pid: None });
+    }
+
+    let child = state.processes.lock().await.remove(&query_id);
+    let Some(mut child) = child else {
+        return Ok(CancelResult { cancelled: false, was_queued: false, killed: false, pid: None });
+    };
+    let pid = child.id();
+    let killed = claude::kill_process_tree(&mut child).is_ok();
+    let _ = child.wait().await;
+    let _ = app.emit("claude-cancelled", serde_json::json!({ "queryId": query_id, "pid": pid }));
+    Ok(CancelResult { cancelled: true, was_queued: false, killed, pid })
+}
+
+/// Kill every running CLI process and drop queued queries (app exit), so no
+/// `claude`/`gemini` processes outlive the app.
+fn kill_all_queries(state: &AppState) {
+    state.queued_queries.lock().unwrap().clear();
+    let mut reg = state.processes.blocking_lock();
+    for (query_id, mut child) in reg.drain() {
+        if let Err(e) = claude::kill_process_tree(&mut child) {
+            eprintln!("Warning: Failed to kill query {} on exit: {}", query_id, e);
+        }
     }
 }
 
+/// Report whether `config` would pipe its message via stdin, without spawning.
+#[tauri::command]
+async fn preview_spawn_plan(config: QueryConfig) -> Result<claude::SpawnPlan, String> {
+    Ok(claude::spawn_plan(&config))
+}
+
+/// Estimate a query's token footprint against the model's context window,
+/// without sending it. The memory and vault CLAUDE.md blocks inside the system
+/// prompt are reported as their own sections (`memory_days` as for
+/// `load_memory_context`).
+#[tauri::command]
+async fn estimate_query_tokens(
+    state: tauri::State<'_, AppState>,
+    config: QueryConfig,
+    memory_days: Option<u32>,
+) -> Result<claude::QueryTokenEstimate, String> {
+    let memory = load_memory_context(state.clone(), memory_days).await.unwrap_or_default();
+    let vault = load_vault_context(state).await.unwrap_or_default();
+    Ok(claude::estimate_query_tokens(&config, &[("memory", &memory), ("vaultContext", &vault)]))
+}
+
+/// Turn raw stderr from a failed query into a category and actionable message.
+#[tauri::command]
+async fn diagnose_failure(stderr_lines: Vec<String>) -> Result<claude::FailureDiagnosis, String> {
+    Ok(claude::diagnose_failure(&stderr_lines))
+}
+
+/// Running queries and the most recent completed query timings.
+#[tauri::command]
+async fn query_timings(limit: Option<usize>) -> Result<claude::QueryTimings, String> {
+    Ok(claude::query_timings(limit.unwrap_or(50)))
+}
+
 /// Check if Claude CLI is available. Reuses the same discovery logic as run_query.
 #[tauri::command]
 async fn check_claude() -> Result<String, String> {
@@ -162,6 +526,18 @@ async fn check_claude() -> Result<String, String> {
     }
 }
 
+/// Resolved Claude CLI path plus its `--version` output, for the Settings page.
+#[tauri::command]
+async fn get_claude_version() -> Result<claude::ClaudeVersion, String> {
+    Ok(claude::claude_version().await)
+}
+
+/// Report the effective PATH and where claude/gemini/node resolve within it.
+#[tauri::command]
+async fn diagnose_path() -> Result<claude::PathDiagnosis, String> {
+    Ok(claude::diagnose_path())
+}
+
 #[tauri::command]
 async fn save_mcp_config(config_json: String) -> Result<String, String> {
     let path = mcp_config_path();
@@ -182,181 +558,827 @@ async fn load_mcp_config() -> Result<String, String> {
     }
 }
 
-#[tauri::command]
-async fn get_mcp_config_path() -> Result<String, String> {
-    let path = mcp_config_path();
-    if path.exists() {
-        Ok(path.to_string_lossy().to_string())
-    } else {
-        Err("No MCP config file".to_string())
+/// Check a single `mcpServers` entry: stdio servers need a string `command`
+/// (with optional string-array `args` and string-map `env`), SSE/HTTP servers a string `url`.
+fn validate_mcp_server(entry: &serde_json::Value) -> Result<(), String> {
+    let obj = entry.as_object().ok_or("entry is not an object")?;
+    match (obj.get("command"), obj.get("url")) {
+        (Some(cmd), _) => {
+            if !cmd.as_str().map(|c| !c.trim().is_empty()).unwrap_or(false) {
+                return Err("`command` must be a non-empty string".to_string());
+            }
+            if let Some(args) = obj.get("args") {
+                let ok = args.as_array().map(|a| a.iter().all(|v| v.is_string())).unwrap_or(false);
+                if !ok {
+                    return Err("`args` must be an array of strings".to_string());
+                }
+            }
+        }
+        (None, Some(url)) => {
+            if !url.as_str().map(|u| u.starts_with("http://") || u.starts_with("https://")).unwrap_or(false) {
+                return Err("`url` must be an http(s) URL".to_string());
+            }
+        }
+        (None, None) => return Err("missing `command` or `url`".to_string()),
     }
+    if let Some(env) = obj.get("env") {
+        let ok = env.as_object().map(|m| m.values().all(|v| v.is_string())).unwrap_or(false);
+        if !ok {
+            return Err("`env` must be an object of string values".to_string());
+        }
+    }
+    Ok(())
 }
 
-#[tauri::command]
-async fn get_settings(state: tauri::State<'_, AppState>) -> Result<Settings, String> {
-    let close_to_tray = *state.close_to_tray.lock().unwrap();
-    let vault_path = state.vault_path.lock().unwrap().clone();
-    let projects = state.projects.lock().unwrap().clone();
-    let active_project_id = state.active_project_id.lock().unwrap().clone();
-    Ok(Settings { close_to_tray, vault_path, projects, active_project_id })
+#[derive(serde::Serialize)]
+struct McpConfigIssue {
+    name: String,
+    reason: String,
 }
 
+/// Validate MCP config JSON without touching disk. Returns one issue per invalid
+/// server (an empty list means the config is valid).
 #[tauri::command]
-async fn save_settings(
-    state: tauri::State<'_, AppState>,
-    settings: Settings,
-) -> Result<(), String> {
-    *state.close_to_tray.lock().unwrap() = settings.close_to_tray;
-    *state.vault_path.lock().unwrap() = settings.vault_path.clone();
-    // Preserve project state (managed separately via save_projects)
-    let projects = state.projects.lock().unwrap().clone();
-    let active_project_id = state.active_project_id.lock().unwrap().clone();
-    save_settings_to_disk(&Settings {
-        close_to_tray: settings.close_to_tray,
-        vault_path: settings.vault_path,
-        projects,
-        active_project_id,
-    })
+async fn validate_mcp_config(config_json: String) -> Result<Vec<McpConfigIssue>, String> {
+    let root: serde_json::Value = serde_json::from_str(&config_json)
+        .map_err(|e| format!("Invalid JSON: {}", e))?;
+    let servers = root.get("mcpServers").and_then(|v| v.as_object())
+        .ok_or("Missing `mcpServers` object")?;
+    Ok(servers.iter()
+        .filter_map(|(name, entry)| validate_mcp_server(entry).err()
+            .map(|reason| McpConfigIssue { name: name.clone(), reason }))
+        .collect())
 }
 
-/// Load the Obsidian vault's CLAUDE.md for system prompt context.
-/// Requires a vault_path to be configured in settings.
-#[tauri::command]
-async fn load_vault_context(state: tauri::State<'_, AppState>) -> Result<String, String> {
-    let vault_dir = state.vault_path.lock().unwrap().clone()
-        .ok_or_else(|| "No Obsidian vault configured. Set a vault path in Settings.".to_string())?;
-    let vault_claude = std::path::Path::new(&vault_dir).join("CLAUDE.md");
-    if vault_claude.exists() {
-        std::fs::read_to_string(&vault_claude)
-            .map_err(|e| format!("Failed to read vault CLAUDE.md: {}", e))
-    } else {
-        Err(format!("CLAUDE.md not found in {}", vault_dir))
-    }
+/// Write via a temp file + rename so a crash never leaves a half-written file.
+fn write_atomic(path: &std::path::Path, contents: &str) -> Result<(), String> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, contents).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
 }
 
-// ── Memory system ──────────────────────────────────────────────────────────
+#[derive(serde::Serialize)]
+struct McpRepairResult {
+    path: String,
+    removed: Vec<McpConfigIssue>,
+    /// True when the file wasn't valid JSON and was replaced by an empty skeleton
+    reset: bool,
+}
 
-/// Load composite memory context: MEMORY.md + today's + yesterday's daily logs.
+/// Repair mcp-config.json: drop invalid server entries, ensure a well-formed
+/// `{ "mcpServers": {} }` skeleton, and write it back atomically. The original
+/// is kept as mcp-config.json.bak.
 #[tauri::command]
-async fn load_memory_context(state: tauri::State<'_, AppState>) -> Result<String, String> {
-    let vault_path = state.vault_path.lock().unwrap().clone();
-    let dir = resolve_memory_dir(&vault_path);
-    let mut sections: Vec<String> = Vec::new();
+async fn repair_mcp_config() -> Result<McpRepairResult, String> {
+    let path = mcp_config_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
+    }
 
-    // Persistent memory
-    let mem_file = dir.join("MEMORY.md");
-    if mem_file.exists() {
-        if let Ok(content) = std::fs::read_to_string(&mem_file) {
-            let trimmed = content.trim();
-            if !trimmed.is_empty() {
-                sections.push(format!("### Persistent Memory\n{}", trimmed));
-            }
+    let original = if path.exists() {
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read config: {}", e))?
+    } else {
+        String::new()
+    };
+
+    let parsed = serde_json::from_str::<serde_json::Value>(&original).ok()
+        .filter(|v| v.is_object());
+    let reset = parsed.is_none() && !original.trim().is_empty();
+    let mut root = parsed.unwrap_or_else(|| serde_json::json!({}));
+
+    let mut removed: Vec<McpConfigIssue> = Vec::new();
+    let servers = root.get("mcpServers").and_then(|v| v.as_object()).cloned().unwrap_or_default();
+    let mut kept = serde_json::Map::new();
+    for (name, entry) in servers {
+        match validate_mcp_server(&entry) {
+            Ok(()) => { kept.insert(name, entry); }
+            Err(reason) => removed.push(McpConfigIssue { name, reason }),
         }
     }
+    root["mcpServers"] = serde_json::Value::Object(kept);
 
-    // Daily logs (today + yesterday)
-    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
-    let yesterday = (chrono::Local::now() - chrono::Duration::days(1))
-        .format("%Y-%m-%d")
-        .to_string();
+    if !original.is_empty() {
+        std::fs::write(path.with_extension("json.bak"), &original)
+            .map_err(|e| format!("Failed to write backup: {}", e))?;
+    }
+    let json = serde_json::to_string_pretty(&root).map_err(|e| e.to_string())?;
+    write_atomic(&path, &json)?;
 
-    let daily_dir = dir.join("daily");
-    for (label, date) in [("Today", &today), ("Yesterday", &yesterday)] {
-        let path = daily_dir.join(format!("{}.md", date));
-        if path.exists() {
-            if let Ok(content) = std::fs::read_to_string(&path) {
-                let trimmed = content.trim();
-                if !trimmed.is_empty() {
-                    sections.push(format!("### {} ({})\n{}", label, date, trimmed));
-                }
-            }
-        }
+    Ok(McpRepairResult {
+        path: path.to_string_lossy().to_string(),
+        removed,
+        reset,
+    })
+}
+
+// ── MCP server management ────────────────────────────────────────────────────
+
+/// Section holding switched-off servers; the CLI only reads `mcpServers`.
+const MCP_DISABLED_KEY: &str = "disabledServers";
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct McpServerInfo {
+    name: String,
+    enabled: bool,
+    /// "stdio" (has `command`) or "http" (has `url`)
+    transport: String,
+    command: Option<String>,
+    url: Option<String>,
+}
+
+/// mcp-config.json as a JSON object (an empty skeleton when missing).
+fn read_mcp_root() -> Result<serde_json::Value, String> {
+    let path = mcp_config_path();
+    if !path.exists() {
+        return Ok(serde_json::json!({ "mcpServers": {} }));
     }
+    let json = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read config: {}", e))?;
+    let root: serde_json::Value = serde_json::from_str(&json)
+        .map_err(|e| format!("Invalid MCP config JSON: {}", e))?;
+    if !root.is_object() {
+        return Err("MCP config is not a JSON object".to_string());
+    }
+    Ok(root)
+}
 
-    if sections.is_empty() {
-        Ok(String::new())
-    } else {
-        Ok(sections.join("\n\n"))
+fn write_mcp_root(root: &serde_json::Value) -> Result<(), String> {
+    let path = mcp_config_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
     }
+    let json = serde_json::to_string_pretty(root).map_err(|e| e.to_string())?;
+    write_atomic(&path, &json)
 }
 
-/// Read a specific file from the memory directory.
-#[tauri::command]
-async fn read_memory_file(state: tauri::State<'_, AppState>, filename: String) -> Result<String, String> {
-    let vault_path = state.vault_path.lock().unwrap().clone();
-    let path = resolve_memory_dir(&vault_path).join(&filename);
-    if path.exists() {
-        std::fs::read_to_string(&path)
-            .map_err(|e| format!("Failed to read memory file: {}", e))
-    } else {
-        Ok(String::new())
+/// Mutable server map for `key`, created if absent.
+fn mcp_section<'a>(root: &'a mut serde_json::Value, key: &str) -> &'a mut serde_json::Map<String, serde_json::Value> {
+    if !root.get(key).is_some_and(|v| v.is_object()) {
+        root[key] = serde_json::json!({});
     }
+    root[key].as_object_mut().expect("section was just made an object")
 }
 
-/// Write (overwrite) a file in the memory directory.
+/// Enabled and disabled MCP servers, sorted by name.
 #[tauri::command]
-async fn write_memory_file(state: tauri::State<'_, AppState>, filename: String, content: String) -> Result<(), String> {
-    let vault_path = state.vault_path.lock().unwrap().clone();
-    let path = resolve_memory_dir(&vault_path).join(&filename);
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create memory dir: {}", e))?;
+async fn list_mcp_servers() -> Result<Vec<McpServerInfo>, String> {
+    let root = read_mcp_root()?;
+    let mut servers: Vec<McpServerInfo> = [("mcpServers", true), (MCP_DISABLED_KEY, false)]
+        .into_iter()
+        .flat_map(|(key, enabled)| {
+            root.get(key).and_then(|v| v.as_object()).into_iter().flatten().map(move |(name, entry)| {
+                let command = entry.get("command").and_then(|v| v.as_str()).map(String::from);
+                let url = entry.get("url").and_then(|v| v.as_str()).map(String::from);
+                McpServerInfo {
+                    name: name.clone(),
+                    enabled,
+                    transport: if command.is_some() { "stdio" } else { "http" }.to_string(),
+                    command,
+                    url,
+                }
+            })
+        })
+        .collect();
+    servers.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(servers)
+}
+
+/// Add an (enabled) MCP server. `config_json` is the server entry object;
+/// it is validated like `validate_mcp_config` does. Names must be unique.
+#[tauri::command]
+async fn add_mcp_server(name: String, config_json: String) -> Result<(), String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Server name is empty".to_string());
     }
-    std::fs::write(&path, &content)
-        .map_err(|e| format!("Failed to write memory file: {}", e))
+    let entry: serde_json::Value = serde_json::from_str(&config_json)
+        .map_err(|e| format!("Invalid JSON: {}", e))?;
+    validate_mcp_server(&entry).map_err(|reason| format!("Invalid server {}: {}", name, reason))?;
+
+    let mut root = read_mcp_root()?;
+    if mcp_section(&mut root, "mcpServers").contains_key(&name)
+        || mcp_section(&mut root, MCP_DISABLED_KEY).contains_key(&name)
+    {
+        return Err(format!("MCP server already exists: {}", name));
+    }
+    mcp_section(&mut root, "mcpServers").insert(name, entry);
+    write_mcp_root(&root)
 }
 
-/// Delete a file from the memory directory. Silently succeeds if file doesn't exist.
+/// Remove an MCP server, enabled or disabled.
 #[tauri::command]
-async fn delete_memory_file(state: tauri::State<'_, AppState>, filename: String) -> Result<(), String> {
-    let vault_path = state.vault_path.lock().unwrap().clone();
-    let path = resolve_memory_dir(&vault_path).join(&filename);
-    if path.exists() {
-        std::fs::remove_file(&path)
-            .map_err(|e| format!("Failed to delete memory file: {}", e))?;
+async fn remove_mcp_server(name: String) -> Result<(), String> {
+    let mut root = read_mcp_root()?;
+    let removed = mcp_section(&mut root, "mcpServers").remove(&name).is_some()
+        | mcp_section(&mut root, MCP_DISABLED_KEY).remove(&name).is_some();
+    if !removed {
+        return Err(format!("MCP server not found: {}", name));
     }
-    Ok(())
+    write_mcp_root(&root)
 }
 
-/// Append content to a file in the memory directory (creates if missing).
+/// Enable or disable an MCP server by moving it between `mcpServers` and
+/// `disabledServers`. A no-op when it's already in the requested state.
 #[tauri::command]
-async fn append_memory(state: tauri::State<'_, AppState>, filename: String, content: String) -> Result<(), String> {
-    let vault_path = state.vault_path.lock().unwrap().clone();
-    let path = resolve_memory_dir(&vault_path).join(&filename);
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create memory dir: {}", e))?;
+async fn set_mcp_server_enabled(name: String, enabled: bool) -> Result<(), String> {
+    let mut root = read_mcp_root()?;
+    let (from, to) = if enabled { (MCP_DISABLED_KEY, "mcpServers") } else { ("mcpServers", MCP_DISABLED_KEY) };
+    match mcp_section(&mut root, from).remove(&name) {
+        Some(entry) => {
+            mcp_section(&mut root, to).insert(name, entry);
+            write_mcp_root(&root)
+        }
+        None if mcp_section(&mut root, to).contains_key(&name) => Ok(()),
+        None => Err(format!("MCP server not found: {}", name)),
     }
-    use std::io::Write;
-    let mut file = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&path)
-        .map_err(|e| format!("Failed to open memory file: {}", e))?;
-    writeln!(file, "{}", content)
-        .map_err(|e| format!("Failed to append to memory file: {}", e))
 }
 
-/// List files in a subdirectory of the memory dir (e.g., "research", "sessions").
-/// Returns an empty vec if the directory doesn't exist.
 #[derive(serde::Serialize)]
-struct MemoryFileInfo {
+struct McpHealth {
     name: String,
-    size: u64,
-    /// Unix timestamp in seconds (most recent first)
-    modified: u64,
+    ok: bool,
+    error: Option<String>,
 }
 
-#[tauri::command]
-async fn list_memory_dir(
-    state: tauri::State<'_, AppState>,
-    subdir: String,
-) -> Result<Vec<MemoryFileInfo>, String> {
-    let vault_path = state.vault_path.lock().unwrap().clone();
-    let dir = resolve_memory_dir(&vault_path).join(&subdir);
+/// How long a server gets to answer `initialize` (or accept a connection).
+const MCP_HEALTH_TIMEOUT_SECS: u64 = 10;
 
-    if !dir.exists() || !dir.is_dir() {
-        return Ok(Vec::new());
+/// Spawn a stdio server and complete the MCP `initialize` handshake.
+async fn probe_stdio_mcp(entry: &serde_json::Value) -> Result<(), String> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+    let command = entry.get("command").and_then(|v| v.as_str()).ok_or("missing `command`")?;
+    let args: Vec<&str> = entry.get("args").and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+
+    // npx/uvx are .cmd shims on Windows, which only run through cmd.exe
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut c = tokio::process::Command::new("cmd.exe");
+        c.arg("/c").arg(command);
+        use std::os::windows::process::CommandExt;
+        c.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        c
+    };
+    #[cfg(not(target_os = "windows"))]
+    let mut cmd = tokio::process::Command::new(command);
+    #[cfg(target_os = "macos")]
+    cmd.env("PATH", claude::effective_path());
+
+    cmd.args(&args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true);
+    if let Some(env) = entry.get("env").and_then(|v| v.as_object()) {
+        cmd.envs(env.iter().filter_map(|(k, v)| v.as_str().map(|v| (k, v))));
+    }
+
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn {}: {}", command, e))?;
+    let mut stdin = child.stdin.take().ok_or("No stdin")?;
+    let stdout = child.stdout.take().ok_or("No stdout")?;
+
+    let initialize = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": { "name": "thunderclaude", "version": env!("CARGO_PKG_VERSION") },
+        },
+    });
+    stdin.write_all(format!("{}\n", initialize).as_bytes()).await
+        .map_err(|e| format!("Failed to send initialize: {}", e))?;
+
+    // Servers may log non-JSON lines first; wait for the response to id 1
+    let mut lines = tokio::io::BufReader::new(stdout).lines();
+    let result = loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => {
+                let Ok(msg) = serde_json::from_str::<serde_json::Value>(&line) else { continue };
+                if msg.get("id").and_then(|v| v.as_i64()) != Some(1) {
+                    continue;
+                }
+                break match msg.get("error") {
+                    Some(err) => Err(format!("initialize failed: {}", err)),
+                    None => Ok(()),
+                };
+            }
+            Ok(None) => break Err("Server exited before answering initialize".to_string()),
+            Err(e) => break Err(format!("Failed to read server output: {}", e)),
+        }
+    };
+    let _ = child.kill().await;
+    result
+}
+
+/// Open a TCP connection to an SSE/HTTP server's host and port.
+async fn probe_http_mcp(url: &str) -> Result<(), String> {
+    let (scheme, rest) = url.split_once("://").ok_or("`url` has no scheme")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let authority = authority.rsplit('@').next().unwrap_or(authority);
+    let has_port = authority.rsplit_once(':').is_some_and(|(_, p)| p.parse::<u16>().is_ok())
+        && !authority.ends_with(']');
+    let addr = if has_port {
+        authority.to_string()
+    } else {
+        format!("{}:{}", authority, if scheme == "https" { 443 } else { 80 })
+    };
+    tokio::net::TcpStream::connect(&addr).await
+        .map(|_| ())
+        .map_err(|e| format!("Failed to connect to {}: {}", addr, e))
+}
+
+/// Probe every enabled MCP server: stdio servers must answer `initialize`,
+/// SSE/HTTP servers must accept a TCP connection. Servers run in parallel,
+/// each limited to 10 seconds.
+#[tauri::command]
+async fn check_mcp_servers() -> Result<Vec<McpHealth>, String> {
+    let root = read_mcp_root()?;
+    let servers = root.get("mcpServers").and_then(|v| v.as_object()).cloned().unwrap_or_default();
+
+    let mut probes = tokio::task::JoinSet::new();
+    for (name, entry) in servers {
+        probes.spawn(async move {
+            let probe = async {
+                validate_mcp_server(&entry)?;
+                match entry.get("url").and_then(|v| v.as_str()) {
+                    Some(url) if entry.get("command").is_none() => probe_http_mcp(url).await,
+                    _ => probe_stdio_mcp(&entry).await,
+                }
+            };
+            let timeout = std::time::Duration::from_secs(MCP_HEALTH_TIMEOUT_SECS);
+            let error = match tokio::time::timeout(timeout, probe).await {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e),
+                Err(_) => Some(format!("No response within {}s", MCP_HEALTH_TIMEOUT_SECS)),
+            };
+            McpHealth { name, ok: error.is_none(), error }
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = probes.join_next().await {
+        results.push(joined.map_err(|e| format!("Health check failed: {}", e))?);
+    }
+    results.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(results)
+}
+
+/// Write the global MCP config filtered to `project`'s enabled servers as
+/// mcp-project-<id>.json and return its path. None when the project enables no
+/// servers or there is no global config. The copy drops `disabledServers`.
+fn project_mcp_config(project: &ProjectConfig) -> Result<Option<String>, String> {
+    let global = mcp_config_path();
+    if project.enabled_mcp_names.is_empty() || !global.exists() {
+        return Ok(None);
+    }
+    let json = std::fs::read_to_string(&global).map_err(|e| format!("Failed to read config: {}", e))?;
+    let mut root: serde_json::Value = serde_json::from_str(&json)
+        .map_err(|e| format!("Invalid MCP config JSON: {}", e))?;
+    if !root.is_object() {
+        return Err("Invalid MCP config JSON: expected an object".to_string());
+    }
+
+    let servers = root.get("mcpServers").and_then(|v| v.as_object()).cloned().unwrap_or_default();
+    let enabled: serde_json::Map<String, serde_json::Value> = servers
+        .into_iter()
+        .filter(|(name, _)| project.enabled_mcp_names.contains(name))
+        .collect();
+    root["mcpServers"] = serde_json::Value::Object(enabled);
+    // App-only bookkeeping the CLI doesn't understand
+    if let Some(obj) = root.as_object_mut() {
+        obj.remove(MCP_DISABLED_KEY);
+    }
+
+    let path = thunderclaude_dir().join(format!("mcp-project-{}.json", project.id));
+    let filtered = serde_json::to_string_pretty(&root).map_err(|e| e.to_string())?;
+    write_atomic(&path, &filtered)?;
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+#[tauri::command]
+async fn get_mcp_config_path() -> Result<String, String> {
+    let path = mcp_config_path();
+    if path.exists() {
+        Ok(path.to_string_lossy().to_string())
+    } else {
+        Err("No MCP config file".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_settings(state: tauri::State<'_, AppState>) -> Result<Settings, String> {
+    Ok(current_settings(&state))
+}
+
+#[tauri::command]
+async fn save_settings(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    search_state: tauri::State<'_, search::SearchState>,
+    mut settings: Settings,
+) -> Result<(), String> {
+    for path in [&mut settings.vault_path, &mut settings.claude_binary_path, &mut settings.gemini_binary_path] {
+        *path = path.as_deref().map(expand_path).filter(|p| !p.is_empty());
+    }
+    *state.close_to_tray.lock().unwrap() = settings.close_to_tray;
+    *state.vault_path.lock().unwrap() = settings.vault_path;
+    *state.auto_index.lock().unwrap() = settings.auto_index;
+    *state.embedding_batch_size.lock().unwrap() = settings.embedding_batch_size;
+    *state.embedding_tokenizer_threads.lock().unwrap() = settings.embedding_tokenizer_threads;
+    search_state.set_embedding_options(settings.embedding_batch_size, settings.embedding_tokenizer_threads);
+    *state.auto_reindex_interval_mins.lock().unwrap() = settings.auto_reindex_interval_mins;
+    restart_auto_reindex(&app, &state);
+    *state.claude_binary_path.lock().unwrap() = settings.claude_binary_path.clone();
+    *state.gemini_binary_path.lock().unwrap() = settings.gemini_binary_path.clone();
+    claude::set_binary_overrides(settings.claude_binary_path, settings.gemini_binary_path);
+    *state.max_file_read_bytes.lock().unwrap() = settings.max_file_read_bytes;
+    *state.soft_delete.lock().unwrap() = settings.soft_delete;
+    *state.restrict_filesystem.lock().unwrap() = settings.restrict_filesystem;
+    *state.max_temp_image_bytes.lock().unwrap() = settings.max_temp_image_bytes;
+    *state.max_temp_images_total_bytes.lock().unwrap() = settings.max_temp_images_total_bytes;
+    {
+        let mut limit = state.max_concurrent_queries.lock().unwrap();
+        if *limit != settings.max_concurrent_queries {
+            *limit = settings.max_concurrent_queries;
+            *state.query_slots.lock().unwrap() = query_semaphore(settings.max_concurrent_queries);
+        }
+    }
+    // Project state is preserved (managed separately via save_projects)
+    save_settings_to_disk(&current_settings(&state))
+}
+
+/// Toggle close-to-tray at runtime and persist it immediately.
+#[tauri::command]
+async fn set_close_to_tray(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.close_to_tray.lock().unwrap() = enabled;
+    save_settings_to_disk(&current_settings(&state))
+}
+
+/// Last component of a path, so shared configs don't reveal directory layouts.
+fn path_basename(path: &str) -> String {
+    std::path::Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Lowercase words of a snake_case, kebab-case or camelCase key.
+fn key_words(key: &str) -> Vec<String> {
+    let mut words = vec![String::new()];
+    let mut prev_lower = false;
+    for c in key.chars() {
+        if c == '_' || c == '-' || (c.is_uppercase() && prev_lower) {
+            words.push(String::new());
+        }
+        if c != '_' && c != '-' {
+            words.last_mut().unwrap().extend(c.to_lowercase());
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+    }
+    words.retain(|w| !w.is_empty());
+    words
+}
+
+/// Whether a key names a secret or an environment, judged by whole words so
+/// e.g. `embedding_tokenizer_threads` isn't caught by "token".
+fn is_sensitive_key(key: &str) -> bool {
+    const SENSITIVE: &[&str] = &[
+        "token", "secret", "secrets", "password", "passwd", "apikey", "env", "environment", "auth",
+        "authorization", "credential", "credentials", "cookie",
+    ];
+    let words = key_words(key);
+    words.iter().any(|w| SENSITIVE.contains(&w.as_str()))
+        || words.windows(2).any(|pair| pair[0] == "api" && pair[1] == "key")
+}
+
+/// Drop any object key that looks like it holds a secret or environment.
+fn strip_sensitive_keys(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|key, _| !is_sensitive_key(key));
+            map.values_mut().for_each(strip_sensitive_keys);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_sensitive_keys),
+        _ => {}
+    }
+}
+
+/// Current settings as pretty JSON that is safe to paste into a bug report:
+/// paths are reduced to basenames, projects to a summary, and any
+/// token/secret/env-like keys are removed.
+#[tauri::command]
+async fn export_settings_redacted(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let settings = current_settings(&state);
+
+    let projects: Vec<serde_json::Value> = settings.projects.iter().map(|p| {
+        serde_json::json!({
+            "id": p.id,
+            "rootDir": path_basename(&p.root_path),
+            "enabledMcpCount": p.enabled_mcp_names.len(),
+            "enabledSkillCount": p.enabled_skill_ids.len(),
+            "defaultModel": p.default_model,
+            "defaultEngine": p.default_engine,
+            "searchIgnoredDirs": p.search_ignored_dirs,
+        })
+    }).collect();
+
+    let mut settings_json = serde_json::to_value(&settings).map_err(|e| e.to_string())?;
+    if let Some(map) = settings_json.as_object_mut() {
+        map.insert("vault_path".to_string(), serde_json::json!(settings.vault_path.as_deref().map(path_basename)));
+        map.insert("claude_binary_path".to_string(), serde_json::json!(settings.claude_binary_path.as_deref().map(path_basename)));
+        map.insert("gemini_binary_path".to_string(), serde_json::json!(settings.gemini_binary_path.as_deref().map(path_basename)));
+        map.insert("projects".to_string(), serde_json::Value::Array(projects));
+    }
+
+    let mut report = serde_json::json!({
+        "appVersion": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "settings": settings_json,
+    });
+    strip_sensitive_keys(&mut report);
+    serde_json::to_string_pretty(&report).map_err(|e| format!("Failed to serialize settings: {}", e))
+}
+
+fn copy_dir_recursive(src: &std::path::Path, dst: &std::path::Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dst)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_recursive(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Move the app data directory (settings, sessions, vectors, analytics, …) to
+/// `new_path` and remember it for future launches. Everything is copied first;
+/// if any copy fails the copies are removed and the old location stays in use.
+/// Originals are deleted only after the switch. Returns the new location.
+#[tauri::command]
+async fn relocate_data_dir(new_path: String) -> Result<String, String> {
+    if std::env::var(DATA_DIR_ENV).is_ok_and(|d| !d.trim().is_empty()) {
+        return Err(format!("Data directory is set by {}; unset it to relocate", DATA_DIR_ENV));
+    }
+
+    let old = thunderclaude_dir();
+    let new = PathBuf::from(new_path.trim());
+    let default = default_thunderclaude_dir();
+    if !new.is_absolute() {
+        return Err(format!("Not an absolute path: {}", new_path));
+    }
+    if new == old {
+        return Ok(new.to_string_lossy().to_string());
+    }
+    if new.starts_with(&old) || old.starts_with(&new) {
+        return Err("The new location can't be inside the current one (or vice versa)".to_string());
+    }
+    // The default dir may hold nothing but the location pointer when moving back
+    let occupied = std::fs::read_dir(&new)
+        .map(|mut entries| entries.any(|e| e.map(|e| e.file_name() != DATA_LOCATION_FILE).unwrap_or(true)))
+        .unwrap_or(false);
+    if occupied {
+        return Err(format!("Target directory is not empty: {}", new.display()));
+    }
+
+    let entries: Vec<std::fs::DirEntry> = match std::fs::read_dir(&old) {
+        Ok(read) => read.flatten().filter(|e| e.file_name() != DATA_LOCATION_FILE).collect(),
+        Err(_) => Vec::new(),
+    };
+
+    // Copy everything; on failure remove what was copied so nothing is half-moved
+    std::fs::create_dir_all(&new).map_err(|e| format!("Failed to create {}: {}", new.display(), e))?;
+    let rollback = |copied: &[PathBuf]| {
+        for path in copied {
+            let _ = if path.is_dir() { std::fs::remove_dir_all(path) } else { std::fs::remove_file(path) };
+        }
+    };
+    let mut copied: Vec<PathBuf> = Vec::new();
+    for entry in &entries {
+        let target = new.join(entry.file_name());
+        let result = match entry.file_type() {
+            Ok(t) if t.is_dir() => copy_dir_recursive(&entry.path(), &target),
+            _ => std::fs::copy(entry.path(), &target).map(|_| ()),
+        };
+        copied.push(target);
+        if let Err(e) = result {
+            rollback(&copied);
+            return Err(format!("Failed to copy {}: {}", entry.path().display(), e));
+        }
+    }
+
+    // Record the new location (none needed when moving back to the default)
+    let pointer = default.join(DATA_LOCATION_FILE);
+    let recorded = if new == default {
+        match std::fs::remove_file(&pointer) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    } else {
+        std::fs::create_dir_all(&default)
+            .and_then(|_| std::fs::write(&pointer, new.to_string_lossy().as_bytes()))
+    };
+    if let Err(e) = recorded {
+        rollback(&copied);
+        return Err(format!("Failed to record new data location: {}", e));
+    }
+    *DATA_DIR.lock().unwrap() = Some(new.clone());
+
+    // All state reads/writes resolve through thunderclaude_dir(), so the app
+    // now runs from the copies; the originals can go.
+    for entry in &entries {
+        let path = entry.path();
+        let removed = if path.is_dir() { std::fs::remove_dir_all(&path) } else { std::fs::remove_file(&path) };
+        if let Err(e) = removed {
+            eprintln!("Warning: Failed to remove old data {}: {}", path.display(), e);
+        }
+    }
+
+    Ok(new.to_string_lossy().to_string())
+}
+
+/// Load the Obsidian vault's CLAUDE.md for system prompt context.
+/// Requires a vault_path to be configured in settings.
+#[tauri::command]
+async fn load_vault_context(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let vault_dir = state.vault_path.lock().unwrap().clone()
+        .ok_or_else(|| "No Obsidian vault configured. Set a vault path in Settings.".to_string())?;
+    let vault_claude = std::path::Path::new(&vault_dir).join("CLAUDE.md");
+    if vault_claude.exists() {
+        std::fs::read_to_string(&vault_claude)
+            .map_err(|e| format!("Failed to read vault CLAUDE.md: {}", e))
+    } else {
+        Err(format!("CLAUDE.md not found in {}", vault_dir))
+    }
+}
+
+// ── Memory system ──────────────────────────────────────────────────────────
+
+/// Most daily logs `load_memory_context` will include, whatever `days` asks for.
+const MAX_MEMORY_LOOKBACK_DAYS: u32 = 30;
+
+/// Load composite memory context: MEMORY.md + the last `days` daily logs
+/// (default 2: today + yesterday, capped at 30).
+#[tauri::command]
+async fn load_memory_context(
+    state: tauri::State<'_, AppState>,
+    days: Option<u32>,
+) -> Result<String, String> {
+    let vault_path = state.vault_path.lock().unwrap().clone();
+    let dir = resolve_memory_dir(&vault_path);
+    let mut sections: Vec<String> = Vec::new();
+
+    // Persistent memory
+    let mem_file = dir.join("MEMORY.md");
+    if mem_file.exists() {
+        if let Ok(content) = std::fs::read_to_string(&mem_file) {
+            let trimmed = content.trim();
+            if !trimmed.is_empty() {
+                sections.push(format!("### Persistent Memory\n{}", trimmed));
+            }
+        }
+    }
+
+    // Daily logs, newest first
+    let days = days.unwrap_or(2).min(MAX_MEMORY_LOOKBACK_DAYS);
+    let now = chrono::Local::now();
+    let daily_dir = dir.join("daily");
+    for offset in 0..days {
+        let date = (now - chrono::Duration::days(offset as i64))
+            .format("%Y-%m-%d")
+            .to_string();
+        let label = match offset {
+            0 => "Today".to_string(),
+            1 => "Yesterday".to_string(),
+            n => format!("{} days ago", n),
+        };
+        let path = daily_dir.join(format!("{}.md", date));
+        if path.exists() {
+            if let Ok(content) = std::fs::read_to_string(&path) {
+                let trimmed = content.trim();
+                if !trimmed.is_empty() {
+                    sections.push(format!("### {} ({})\n{}", label, date, trimmed));
+                }
+            }
+        }
+    }
+
+    if sections.is_empty() {
+        Ok(String::new())
+    } else {
+        Ok(sections.join("\n\n"))
+    }
+}
+
+/// Read a specific file from the memory directory.
+#[tauri::command]
+async fn read_memory_file(state: tauri::State<'_, AppState>, filename: String) -> Result<String, String> {
+    let vault_path = state.vault_path.lock().unwrap().clone();
+    let path = join_within(&resolve_memory_dir(&vault_path), &filename)?;
+    if path.exists() {
+        std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read memory file: {}", e))
+    } else {
+        Ok(String::new())
+    }
+}
+
+/// Write (overwrite) a file in the memory directory.
+#[tauri::command]
+async fn write_memory_file(state: tauri::State<'_, AppState>, filename: String, content: String) -> Result<(), String> {
+    let vault_path = state.vault_path.lock().unwrap().clone();
+    let path = join_within(&resolve_memory_dir(&vault_path), &filename)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create memory dir: {}", e))?;
+    }
+    std::fs::write(&path, &content)
+        .map_err(|e| format!("Failed to write memory file: {}", e))
+}
+
+/// Delete a file from the memory directory. Silently succeeds if file doesn't exist.
+#[tauri::command]
+async fn delete_memory_file(state: tauri::State<'_, AppState>, filename: String) -> Result<(), String> {
+    let vault_path = state.vault_path.lock().unwrap().clone();
+    let path = join_within(&resolve_memory_dir(&vault_path), &filename)?;
+    if path.exists() {
+        remove_file_maybe_trash(&path, *state.soft_delete.lock().unwrap())
+            .map_err(|e| format!("Failed to delete memory file: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Rename (or move) a file within the memory directory. Errors if the target exists.
+#[tauri::command]
+async fn rename_memory_file(state: tauri::State<'_, AppState>, from: String, to: String) -> Result<(), String> {
+    let vault_path = state.vault_path.lock().unwrap().clone();
+    let dir = resolve_memory_dir(&vault_path);
+    let (from_path, to_path) = (join_within(&dir, &from)?, join_within(&dir, &to)?);
+    if !from_path.exists() {
+        return Err(format!("Memory file not found: {}", from));
+    }
+    if to_path.exists() {
+        return Err(format!("Memory file already exists: {}", to));
+    }
+    if let Some(parent) = to_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create memory dir: {}", e))?;
+    }
+    std::fs::rename(&from_path, &to_path)
+        .map_err(|e| format!("Failed to rename memory file: {}", e))
+}
+
+/// Append content to a file in the memory directory (creates if missing).
+#[tauri::command]
+async fn append_memory(state: tauri::State<'_, AppState>, filename: String, content: String) -> Result<(), String> {
+    let vault_path = state.vault_path.lock().unwrap().clone();
+    let path = join_within(&resolve_memory_dir(&vault_path), &filename)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create memory dir: {}", e))?;
+    }
+    use std::io::Write;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open memory file: {}", e))?;
+    writeln!(file, "{}", content)
+        .map_err(|e| format!("Failed to append to memory file: {}", e))
+}
+
+/// List files in a subdirectory of the memory dir (e.g., "research", "sessions").
+/// Returns an empty vec if the directory doesn't exist.
+#[derive(serde::Serialize)]
+struct MemoryFileInfo {
+    name: String,
+    size: u64,
+    /// Unix timestamp in seconds (most recent first)
+    modified: u64,
+    /// Matching line, for `search_memory` results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snippet: Option<String>,
+}
+
+#[tauri::command]
+async fn list_memory_dir(
+    state: tauri::State<'_, AppState>,
+    subdir: String,
+) -> Result<Vec<MemoryFileInfo>, String> {
+    let vault_path = state.vault_path.lock().unwrap().clone();
+    let dir = join_within(&resolve_memory_dir(&vault_path), &subdir)?;
+
+    if !dir.exists() || !dir.is_dir() {
+        return Ok(Vec::new());
     }
 
     let read_dir = std::fs::read_dir(&dir)
@@ -383,98 +1405,505 @@ async fn list_memory_dir(
             name,
             size,
             modified,
+            snippet: None,
         });
     }
 
     // Most recent first
+    #[allow(clippy::unnecessary_sort_by)]
     entries.sort_by(|a, b| b.modified.cmp(&a.modified));
 
     Ok(entries)
 }
 
+/// Longest `search_memory` snippet, in characters.
+const MEMORY_SNIPPET_CHARS: usize = 200;
+
+/// First line of `content` containing `needle` (already lowercased), trimmed
+/// and cut to `MEMORY_SNIPPET_CHARS` around the match.
+fn memory_snippet(content: &str, needle: &str) -> Option<String> {
+    let line = content.lines().find(|l| l.to_lowercase().contains(needle))?.trim();
+    let chars: Vec<char> = line.chars().collect();
+    if chars.len() <= MEMORY_SNIPPET_CHARS {
+        return Some(line.to_string());
+    }
+    let lower = line.to_lowercase();
+    let at_chars = lower[..lower.find(needle).unwrap_or(0)].chars().count();
+    let start = at_chars.saturating_sub(MEMORY_SNIPPET_CHARS / 3).min(chars.len() - MEMORY_SNIPPET_CHARS);
+    let end = start + MEMORY_SNIPPET_CHARS;
+    let mut snippet: String = chars[start..end].iter().collect();
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+/// Case-insensitive search across every .md file under the memory dir
+/// (recursively). Names are memory-relative paths; most recent first.
+#[tauri::command]
+async fn search_memory(
+    state: tauri::State<'_, AppState>,
+    query: String,
+) -> Result<Vec<MemoryFileInfo>, String> {
+    let needle = query.trim().to_lowercase();
+    if needle.is_empty() {
+        return Ok(Vec::new());
+    }
+    let vault_path = state.vault_path.lock().unwrap().clone();
+    let dir = resolve_memory_dir(&vault_path);
+
+    tokio::task::spawn_blocking(move || {
+        let mut results: Vec<MemoryFileInfo> = Vec::new();
+        walk_vault(&dir, |f| {
+            let Ok(content) = std::fs::read_to_string(dir.join(&f.path)) else { return };
+            if let Some(snippet) = memory_snippet(&content, &needle) {
+                results.push(MemoryFileInfo {
+                    name: f.path,
+                    size: f.size,
+                    modified: f.modified,
+                    snippet: Some(snippet),
+                });
+            }
+        });
+        results.sort_by_key(|e| std::cmp::Reverse(e.modified));
+        results
+    })
+    .await
+    .map_err(|e| format!("Memory search failed: {}", e))
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MemorySubdirInfo {
+    name: String,
+    /// Direct children (files and folders)
+    child_count: usize,
+    /// Unix seconds of the most recently modified direct child (or the folder itself)
+    modified: u64,
+}
+
+fn modified_secs(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// List the folders inside a memory subdirectory ("" = the memory root), most
+/// recently touched first, for navigating nested research/sessions trees.
+/// Returns an empty vec if the directory doesn't exist.
+#[tauri::command]
+async fn list_memory_subdirs(
+    state: tauri::State<'_, AppState>,
+    subdir: String,
+) -> Result<Vec<MemorySubdirInfo>, String> {
+    let vault_path = state.vault_path.lock().unwrap().clone();
+    let dir = join_within(&resolve_memory_dir(&vault_path), &subdir)?;
+
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let read_dir = std::fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to read memory dir: {}", e))?;
+
+    let mut entries: Vec<MemorySubdirInfo> = Vec::new();
+    for entry in read_dir.flatten() {
+        let metadata = match entry.metadata() {
+            Ok(m) if m.is_dir() => m,
+            _ => continue,
+        };
+
+        let mut child_count = 0;
+        let mut modified = modified_secs(&metadata);
+        for child in std::fs::read_dir(entry.path()).into_iter().flatten().flatten() {
+            child_count += 1;
+            if let Ok(m) = child.metadata() {
+                modified = modified.max(modified_secs(&m));
+            }
+        }
+
+        entries.push(MemorySubdirInfo {
+            name: entry.file_name().to_string_lossy().to_string(),
+            child_count,
+            modified,
+        });
+    }
+
+    entries.sort_by_key(|e| std::cmp::Reverse(e.modified));
+
+    Ok(entries)
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultDetection {
+    is_vault: bool,
+    /// Vault name: the folder name, as Obsidian displays it
+    name: Option<String>,
+}
+
+/// Check whether `path` is an Obsidian vault (has a `.obsidian/` directory).
+/// Used by the vault picker and to warn about pointing `vault_path` elsewhere.
+#[tauri::command]
+async fn is_obsidian_vault(path: String) -> Result<VaultDetection, String> {
+    let dir = std::path::Path::new(&path);
+    if !dir.is_dir() {
+        return Err(format!("Not a valid directory: {}", path));
+    }
+    let is_vault = dir.join(".obsidian").is_dir();
+    let name = if is_vault {
+        dir.file_name().map(|n| n.to_string_lossy().to_string())
+    } else {
+        None
+    };
+    Ok(VaultDetection { is_vault, name })
+}
+
 // ── Vault scanning (for hybrid search indexing) ──────────────────────────────
 
 #[derive(serde::Serialize)]
-struct VaultFile {
-    path: String,
-    modified: u64,
-    size: u64,
+struct VaultFile {
+    path: String,
+    modified: u64,
+    size: u64,
+}
+
+/// Walk the vault depth-first, calling `on_file` for every .md file (relative,
+/// forward-slash path). Skips: .obsidian/, .git/, .trash/, node_modules/
+fn walk_vault(root: &std::path::Path, mut on_file: impl FnMut(VaultFile)) {
+    let ignored: std::collections::HashSet<&str> = [
+        ".obsidian", ".git", ".trash", "node_modules", ".DS_Store",
+    ].into_iter().collect();
+
+    let mut stack: Vec<std::path::PathBuf> = vec![root.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let metadata = match entry.metadata() {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+
+            if metadata.is_dir() {
+                if !ignored.contains(name.as_str()) {
+                    stack.push(entry.path());
+                }
+                continue;
+            }
+
+            // Only index .md files
+            if !name.ends_with(".md") {
+                continue;
+            }
+
+            let rel_path = entry.path()
+                .strip_prefix(root)
+                .unwrap_or(entry.path().as_path())
+                .to_string_lossy()
+                .replace('\\', "/"); // normalize to forward slashes
+
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+
+            on_file(VaultFile {
+                path: rel_path,
+                modified,
+                size: metadata.len(),
+            });
+        }
+    }
+}
+
+/// Resolve the configured vault root, erroring if unset or missing.
+fn vault_root(state: &AppState) -> Result<PathBuf, String> {
+    let vault_path = state.vault_path.lock().unwrap().clone()
+        .ok_or_else(|| "No Obsidian vault configured. Set a vault path in Settings.".to_string())?;
+    let root = PathBuf::from(&vault_path);
+    if !root.exists() || !root.is_dir() {
+        return Err(format!("Vault path does not exist: {}", vault_path));
+    }
+    Ok(root)
+}
+
+/// Recursively scan the Obsidian vault for .md files.
+/// Returns relative paths, modification timestamps, and file sizes.
+#[tauri::command]
+async fn scan_vault(state: tauri::State<'_, AppState>) -> Result<Vec<VaultFile>, String> {
+    let root = vault_root(&state)?;
+
+    let mut files: Vec<VaultFile> = Vec::new();
+    walk_vault(&root, |f| files.push(f));
+
+    // Sort by modification time (most recent first)
+    #[allow(clippy::unnecessary_sort_by)]
+    files.sort_by(|a, b| b.modified.cmp(&a.modified));
+
+    Ok(files)
+}
+
+const VAULT_SCAN_BATCH_SIZE: usize = 500;
+
+/// Streaming variant of `scan_vault` for large vaults: walks the tree on a
+/// blocking thread and emits `vault-scan-batch` events (up to 500 files each,
+/// in walk order) followed by `vault-scan-done` with the total count.
+#[tauri::command]
+async fn scan_vault_streaming(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<usize, String> {
+    let root = vault_root(&state)?;
+
+    let total = tokio::task::spawn_blocking(move || {
+        let mut batch: Vec<VaultFile> = Vec::with_capacity(VAULT_SCAN_BATCH_SIZE);
+        let mut total = 0usize;
+        walk_vault(&root, |f| {
+            batch.push(f);
+            total += 1;
+            if batch.len() >= VAULT_SCAN_BATCH_SIZE {
+                let _ = app.emit("vault-scan-batch", &batch);
+                batch.clear();
+            }
+        });
+        if !batch.is_empty() {
+            let _ = app.emit("vault-scan-batch", &batch);
+        }
+        let _ = app.emit("vault-scan-done", serde_json::json!({ "total": total }));
+        total
+    })
+    .await
+    .map_err(|e| format!("Vault scan failed: {}", e))?;
+
+    Ok(total)
+}
+
+/// Map a link target to its renamed form if it points at `old` (compared
+/// case-insensitively, like Obsidian). `old`/`new` are vault paths without ".md";
+/// a bare note name is matched against the old file name.
+fn renamed_link_target(target: &str, old: &str, new: &str) -> Option<String> {
+    let (bare, ext) = match target.strip_suffix(".md") {
+        Some(b) => (b, ".md"),
+        None => (target, ""),
+    };
+    let old_stem = old.rsplit('/').next().unwrap_or(old);
+    let new_stem = new.rsplit('/').next().unwrap_or(new);
+    let bare_lower = bare.trim().to_lowercase();
+    let replaced = if bare_lower == old.to_lowercase() {
+        new
+    } else if bare_lower == old_stem.to_lowercase() {
+        new_stem
+    } else {
+        return None;
+    };
+    Some(format!("{}{}", replaced, ext))
+}
+
+/// Resolve `target` against the vault directory `dir`, folding `.` and `..`.
+/// A leading `/` makes the target vault-absolute. None if it escapes the vault.
+fn resolve_vault_link(dir: &str, target: &str) -> Option<String> {
+    let mut parts: Vec<&str> = Vec::new();
+    if !target.starts_with('/') {
+        parts.extend(dir.split('/').filter(|p| !p.is_empty()));
+    }
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            p => parts.push(p),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Path of the vault file `to` relative to the vault directory `from_dir`.
+fn relative_vault_link(from_dir: &str, to: &str) -> String {
+    let from: Vec<&str> = from_dir.split('/').filter(|p| !p.is_empty()).collect();
+    let to: Vec<&str> = to.split('/').filter(|p| !p.is_empty()).collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts = vec![".."; from.len() - common];
+    parts.extend(&to[common..]);
+    parts.join("/")
+}
+
+/// Map a markdown link target written relative to the linking note's directory
+/// to its renamed form, keeping it relative to that directory.
+fn renamed_relative_link(target: &str, note_dir: &str, old: &str, new: &str) -> Option<String> {
+    let (bare, ext) = match target.strip_suffix(".md") {
+        Some(b) => (b, ".md"),
+        None => (target, ""),
+    };
+    let resolved = resolve_vault_link(note_dir, bare.trim())?;
+    if resolved.to_lowercase() != old.to_lowercase() {
+        return None;
+    }
+    let rel = if target.starts_with('/') {
+        format!("/{}", new)
+    } else {
+        relative_vault_link(note_dir, new)
+    };
+    Some(format!("{}{}", rel, ext))
+}
+
+/// Rewrite `[[old]]` / `[[old|alias]]` / `[[old#heading]]` wikilinks and
+/// `[text](old.md)` markdown links to point at `new_rel`. Markdown links are
+/// resolved against `note_rel`'s directory first, then matched vault-wide.
+/// Returns None when the note has no links to `old_rel`.
+fn rewrite_note_links(content: &str, note_rel: &str, old_rel: &str, new_rel: &str) -> Option<String> {
+    let old = old_rel.strip_suffix(".md").unwrap_or(old_rel);
+    let new = new_rel.strip_suffix(".md").unwrap_or(new_rel);
+    let note_dir = note_rel.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+    let mut changed = false;
+
+    // Pass 1: wikilinks (also covers ![[embeds]])
+    let mut wiki = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let end = match after.find("]]") {
+            Some(e) => e,
+            None => break,
+        };
+        let inner = &after[..end];
+        let cut = inner.find(['|', '#']).unwrap_or(inner.len());
+        wiki.push_str(&rest[..start + 2]);
+        match renamed_link_target(&inner[..cut], old, new) {
+            Some(target) => {
+                wiki.push_str(&target);
+                wiki.push_str(&inner[cut..]);
+                changed = true;
+            }
+            None => wiki.push_str(inner),
+        }
+        wiki.push_str("]]");
+        rest = &after[end + 2..];
+    }
+    wiki.push_str(rest);
+
+    // Pass 2: markdown links to local notes (spaces may be %20-encoded)
+    let mut out = String::with_capacity(wiki.len());
+    let mut rest = wiki.as_str();
+    while let Some(start) = rest.find("](") {
+        let after = &rest[start + 2..];
+        let end = match after.find(')') {
+            Some(e) => e,
+            None => break,
+        };
+        let inner = &after[..end];
+        let cut = inner.find('#').unwrap_or(inner.len());
+        let encoded = inner.contains("%20");
+        let target = inner[..cut].replace("%20", " ");
+        out.push_str(&rest[..start + 2]);
+        let renamed = renamed_relative_link(&target, note_dir, old, new)
+            .or_else(|| renamed_link_target(&target, old, new))
+            .filter(|_| !inner.contains("://"));
+        match renamed {
+            Some(t) => {
+                out.push_str(&if encoded { t.replace(' ', "%20") } else { t });
+                out.push_str(&inner[cut..]);
+                changed = true;
+            }
+            None => out.push_str(inner),
+        }
+        out.push(')');
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+
+    if changed { Some(out) } else { None }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultRenameResult {
+    /// Notes whose links were (or, in preview, would be) rewritten
+    updated_files: Vec<String>,
+    renamed: bool,
 }
 
-/// Recursively scan the Obsidian vault for .md files.
-/// Returns relative paths, modification timestamps, and file sizes.
-/// Skips: .obsidian/, .git/, .trash/, node_modules/
+/// Rename a vault note and optionally rewrite links to it across the vault.
+/// With `preview`, nothing is written — the result lists the notes that would change.
+/// Chunks indexed under the old path are dropped so search doesn't return stale results.
 #[tauri::command]
-async fn scan_vault(state: tauri::State<'_, AppState>) -> Result<Vec<VaultFile>, String> {
-    let vault_path = state.vault_path.lock().unwrap().clone()
-        .ok_or_else(|| "No Obsidian vault configured. Set a vault path in Settings.".to_string())?;
-
-    let root = std::path::Path::new(&vault_path);
-    if !root.exists() || !root.is_dir() {
-        return Err(format!("Vault path does not exist: {}", vault_path));
+async fn rename_vault_note(
+    state: tauri::State<'_, AppState>,
+    search_state: tauri::State<'_, search::SearchState>,
+    old_path: String,
+    new_path: String,
+    update_links: bool,
+    preview: Option<bool>,
+) -> Result<VaultRenameResult, String> {
+    let root = vault_root(&state)?;
+    let preview = preview.unwrap_or(false);
+    let (old_rel, new_rel) = (old_path.replace('\\', "/"), new_path.replace('\\', "/"));
+    let (from, to) = (join_within(&root, &old_rel)?, join_within(&root, &new_rel)?);
+
+    if !from.is_file() {
+        return Err(format!("Note not found: {}", old_rel));
+    }
+    if to.exists() {
+        return Err(format!("Already exists: {}", new_rel));
     }
 
-    let ignored: std::collections::HashSet<&str> = [
-        ".obsidian", ".git", ".trash", "node_modules", ".DS_Store",
-    ].into_iter().collect();
-
-    let mut files: Vec<VaultFile> = Vec::new();
-    let mut stack: Vec<std::path::PathBuf> = vec![root.to_path_buf()];
-
-    while let Some(dir) = stack.pop() {
-        let entries = match std::fs::read_dir(&dir) {
-            Ok(e) => e,
-            Err(_) => continue,
-        };
-
-        for entry in entries.flatten() {
-            let name = entry.file_name().to_string_lossy().to_string();
-            let metadata = match entry.metadata() {
-                Ok(m) => m,
+    // Compute rewrites up front, but only write them once the rename succeeded,
+    // so a failed rename never leaves links pointing at a note that doesn't exist.
+    let mut rewrites: Vec<(String, String)> = Vec::new();
+    if update_links {
+        let mut notes: Vec<VaultFile> = Vec::new();
+        walk_vault(&root, |f| notes.push(f));
+        for note in notes {
+            let content = match std::fs::read_to_string(root.join(&note.path)) {
+                Ok(c) => c,
                 Err(_) => continue,
             };
-
-            if metadata.is_dir() {
-                if !ignored.contains(name.as_str()) {
-                    stack.push(entry.path());
-                }
-                continue;
+            if let Some(rewritten) = rewrite_note_links(&content, &note.path, &old_rel, &new_rel) {
+                rewrites.push((note.path, rewritten));
             }
-
-            // Only index .md files
-            if !name.ends_with(".md") {
-                continue;
-            }
-
-            let rel_path = entry.path()
-                .strip_prefix(root)
-                .unwrap_or(entry.path().as_path())
-                .to_string_lossy()
-                .replace('\\', "/"); // normalize to forward slashes
-
-            let modified = metadata
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs())
-                .unwrap_or(0);
-
-            files.push(VaultFile {
-                path: rel_path,
-                modified,
-                size: metadata.len(),
-            });
         }
     }
+    let updated_files: Vec<String> = rewrites.iter().map(|(p, _)| p.clone()).collect();
 
-    // Sort by modification time (most recent first)
-    files.sort_by(|a, b| b.modified.cmp(&a.modified));
+    if preview {
+        return Ok(VaultRenameResult { updated_files, renamed: false });
+    }
 
-    Ok(files)
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::rename(&from, &to).map_err(|e| format!("Failed to rename note: {}", e))?;
+    search::remove_chunks_by_source(&search_state, &old_rel).await;
+
+    for (note_path, rewritten) in rewrites {
+        // The renamed note may link to itself — it now lives at the new path
+        let path = if note_path == old_rel { to.clone() } else { root.join(&note_path) };
+        std::fs::write(&path, rewritten).map_err(|e| {
+            format!("Renamed note, but failed to update links in {}: {}", note_path, e)
+        })?;
+    }
+
+    Ok(VaultRenameResult { updated_files, renamed: true })
 }
 
 /// Read the content of multiple vault files in a batch.
-/// Returns pairs of (relative_path, content). Skips files that fail to read.
+/// Returns pairs of (relative_path, content). Skips files that fail to read;
+/// errors on paths outside the vault.
 #[tauri::command]
 async fn read_vault_files(
     state: tauri::State<'_, AppState>,
@@ -487,7 +1916,7 @@ async fn read_vault_files(
     let mut results: Vec<(String, String)> = Vec::new();
 
     for rel_path in &paths {
-        let full_path = root.join(rel_path);
+        let full_path = join_within(root, rel_path)?;
         if let Ok(content) = std::fs::read_to_string(&full_path) {
             results.push((rel_path.clone(), content));
         }
@@ -496,6 +1925,35 @@ async fn read_vault_files(
     Ok(results)
 }
 
+/// Largest region `read_chunk_region` will return in one call.
+const MAX_CHUNK_REGION_BYTES: u64 = 64 * 1024;
+
+/// Read a byte region of a vault note (as stored in chunk metadata at embed time)
+/// without loading the whole file. Invalid UTF-8 at the region edges is replaced.
+#[tauri::command]
+async fn read_chunk_region(
+    state: tauri::State<'_, AppState>,
+    source: String,
+    offset: u64,
+    len: u64,
+) -> Result<String, String> {
+    use std::io::{Read, Seek, SeekFrom};
+    let vault_path = state.vault_path.lock().unwrap().clone()
+        .ok_or_else(|| "No Obsidian vault configured.".to_string())?;
+
+    let full_path = join_within(std::path::Path::new(&vault_path), &source)?;
+    let mut file = std::fs::File::open(&full_path)
+        .map_err(|e| format!("Failed to open {}: {}", source, e))?;
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("Failed to seek in {}: {}", source, e))?;
+
+    let mut buf = Vec::new();
+    file.take(len.min(MAX_CHUNK_REGION_BYTES))
+        .read_to_end(&mut buf)
+        .map_err(|e| format!("Failed to read {}: {}", source, e))?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
 // ── Session storage (filesystem-backed) ──────────────────────────────────────
 
 fn sessions_dir() -> PathBuf {
@@ -520,6 +1978,9 @@ struct SessionIndex {
     last_activity: f64,
     #[serde(default)]
     pinned: bool,
+    /// Imported via `import_readonly`: viewable, never resumable
+    #[serde(default)]
+    readonly: bool,
 }
 
 /// Full session data (with messages) — saved as individual JSON files.
@@ -536,6 +1997,8 @@ struct SessionData {
     last_activity: f64,
     #[serde(default)]
     pinned: bool,
+    #[serde(default)]
+    readonly: bool,
     messages: serde_json::Value,
 }
 
@@ -566,11 +2029,20 @@ fn write_sessions_index(sessions: &[SessionIndex]) -> Result<(), String> {
 
 /// Save a full session (messages + metadata). Updates the index atomically.
 #[tauri::command]
-async fn save_session_file(session: SessionData) -> Result<(), String> {
+async fn save_session_file(mut session: SessionData) -> Result<(), String> {
     let dir = sessions_dir();
     std::fs::create_dir_all(&dir)
         .map_err(|e| format!("Failed to create sessions dir: {}", e))?;
 
+    // Read-only is sticky: a re-save from the frontend can't make it resumable
+    let mut index = list_sessions_internal()?;
+    if index.iter().any(|s| s.id == session.id && s.readonly) {
+        session.readonly = true;
+    }
+    if session.readonly {
+        session.session_id = None;
+    }
+
     // Write the full session data to its own file
     let file_path = dir.join(format!("{}.json", session.id));
     let data_json = serde_json::to_string(&session)
@@ -579,7 +2051,6 @@ async fn save_session_file(session: SessionData) -> Result<(), String> {
         .map_err(|e| format!("Failed to write session file: {}", e))?;
 
     // Update the index
-    let mut index = list_sessions_internal()?;
     let entry = SessionIndex {
         id: session.id.clone(),
         session_id: session.session_id,
@@ -589,6 +2060,7 @@ async fn save_session_file(session: SessionData) -> Result<(), String> {
         timestamp: session.timestamp,
         last_activity: session.last_activity,
         pinned: session.pinned,
+        readonly: session.readonly,
     };
 
     if let Some(pos) = index.iter().position(|s| s.id == session.id) {
@@ -622,6 +2094,19 @@ fn list_sessions_internal() -> Result<Vec<SessionIndex>, String> {
 /// Load a full session by ID (messages included).
 #[tauri::command]
 async fn load_session_file(id: String) -> Result<SessionData, String> {
+    load_session_internal(&id)
+}
+
+/// Internal helper (no Tauri wrapper) for reading a full session data file.
+/// Whether the index marks a session as a read-only import. An unreadable index
+/// counts as not read-only rather than failing the caller.
+fn session_is_readonly(id: &str) -> bool {
+    list_sessions_internal()
+        .map(|index| index.iter().any(|s| s.id == id && s.readonly))
+        .unwrap_or(false)
+}
+
+fn load_session_internal(id: &str) -> Result<SessionData, String> {
     let path = sessions_dir().join(format!("{}.json", id));
     if !path.exists() {
         return Err(format!("Session not found: {}", id));
@@ -632,13 +2117,258 @@ async fn load_session_file(id: String) -> Result<SessionData, String> {
         .map_err(|e| format!("Failed to parse session: {}", e))
 }
 
+/// One window of a session's messages, for incremental loading.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionMessagesPage {
+    messages: Vec<serde_json::Value>,
+    offset: usize,
+    /// Messages in the whole session
+    total: usize,
+}
+
+/// Load messages `[offset, offset + limit)` of a session without shipping the
+/// rest to the frontend. Offsets past the end yield an empty page.
+#[tauri::command]
+async fn get_session_messages_page(id: String, offset: usize, limit: usize) -> Result<SessionMessagesPage, String> {
+    // Only the messages array; the other fields are skipped while parsing
+    #[derive(serde::Deserialize)]
+    struct MessagesOnly {
+        #[serde(default)]
+        messages: Vec<serde_json::Value>,
+    }
+
+    let path = sessions_dir().join(format!("{}.json", id));
+    if !path.exists() {
+        return Err(format!("Session not found: {}", id));
+    }
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read session: {}", e))?;
+    let mut all: MessagesOnly = serde_json::from_str(&json)
+        .map_err(|e| format!("Failed to parse session: {}", e))?;
+
+    let total = all.messages.len();
+    let start = offset.min(total);
+    let end = start.saturating_add(limit).min(total);
+    Ok(SessionMessagesPage {
+        messages: all.messages.drain(start..end).collect(),
+        offset: start,
+        total,
+    })
+}
+
+/// Where two versions of a session diverge. Ranges are half-open `[start, end)`
+/// message indices; everything outside them is identical in both versions.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionDiff {
+    len_a: usize,
+    len_b: usize,
+    /// First index where the message arrays differ (None = identical)
+    first_difference: Option<usize>,
+    /// Turns present in A but not B
+    removed: (usize, usize),
+    /// Turns present in B but not A
+    added: (usize, usize),
+}
+
+fn session_messages(data: &SessionData) -> &[serde_json::Value] {
+    data.messages.as_array().map(|v| v.as_slice()).unwrap_or(&[])
+}
+
+/// Compare the messages of two sessions turn-by-turn (read-only).
+/// Trims the common prefix and suffix so an edit in the middle of a long
+/// conversation reports only the differing window.
+#[tauri::command]
+async fn diff_sessions(id_a: String, id_b: String) -> Result<SessionDiff, String> {
+    let a = load_session_internal(&id_a)?;
+    let b = load_session_internal(&id_b)?;
+    let (ma, mb) = (session_messages(&a), session_messages(&b));
+
+    let prefix = ma.iter().zip(mb).take_while(|(x, y)| x == y).count();
+    if prefix == ma.len() && prefix == mb.len() {
+        return Ok(SessionDiff {
+            len_a: ma.len(),
+            len_b: mb.len(),
+            first_difference: None,
+            removed: (prefix, prefix),
+            added: (prefix, prefix),
+        });
+    }
+
+    // Common suffix, never overlapping the prefix
+    let suffix = ma[prefix..].iter().rev()
+        .zip(mb[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    Ok(SessionDiff {
+        len_a: ma.len(),
+        len_b: mb.len(),
+        first_difference: Some(prefix),
+        removed: (prefix, ma.len() - suffix),
+        added: (prefix, mb.len() - suffix),
+    })
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionStats {
+    /// User + assistant messages (system notes excluded)
+    turn_count: usize,
+    user_chars: usize,
+    assistant_chars: usize,
+    tool_call_count: usize,
+    distinct_tools_used: Vec<String>,
+    /// Unix milliseconds
+    first_timestamp: Option<f64>,
+    last_timestamp: Option<f64>,
+    /// last - first, in milliseconds
+    duration_span: Option<f64>,
+}
+
+/// Message timestamp as Unix ms: numbers as stored by the frontend, or
+/// RFC 3339 strings as emitted by the Gemini CLI.
+fn message_timestamp_ms(msg: &serde_json::Value) -> Option<f64> {
+    match msg.get("timestamp")? {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => chrono::DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|t| t.timestamp_millis() as f64),
+        _ => None,
+    }
+}
+
+/// Text length and tool names in a message. Content may be a plain string or a
+/// Claude-style block array; tool calls come from `toolCalls` or `tool_use`
+/// blocks, named by `name` (Claude) or `tool_name` (Gemini).
+fn message_chars_and_tools(msg: &serde_json::Value) -> (usize, Vec<String>) {
+    let tool_name = |v: &serde_json::Value| {
+        v.get("name").or_else(|| v.get("tool_name"))
+            .and_then(|n| n.as_str())
+            .map(|n| n.to_string())
+    };
+
+    let mut chars = 0;
+    let mut tools: Vec<String> = Vec::new();
+    match msg.get("content") {
+        Some(serde_json::Value::String(text)) => chars += text.chars().count(),
+        Some(serde_json::Value::Array(blocks)) => {
+            for block in blocks {
+                match block.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => {
+                        chars += block.get("text").and_then(|t| t.as_str())
+                            .map(|t| t.chars().count())
+                            .unwrap_or(0);
+                    }
+                    Some("tool_use") => tools.extend(tool_name(block)),
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+    if let Some(calls) = msg.get("toolCalls").and_then(|c| c.as_array()) {
+        tools.extend(calls.iter().filter_map(tool_name));
+    }
+    (chars, tools)
+}
+
+/// Summarize a stored conversation's shape (turns, characters per role, tool
+/// usage, time span). Unrecognized message shapes are skipped, not errors.
+#[tauri::command]
+async fn session_stats(id: String) -> Result<SessionStats, String> {
+    let session = load_session_internal(&id)?;
+
+    let mut stats = SessionStats {
+        turn_count: 0,
+        user_chars: 0,
+        assistant_chars: 0,
+        tool_call_count: 0,
+        distinct_tools_used: Vec::new(),
+        first_timestamp: None,
+        last_timestamp: None,
+        duration_span: None,
+    };
+    let mut tools = std::collections::BTreeSet::new();
+
+    for msg in session_messages(&session) {
+        let (chars, msg_tools) = message_chars_and_tools(msg);
+        match msg.get("role").and_then(|r| r.as_str()) {
+            Some("user") => {
+                stats.turn_count += 1;
+                stats.user_chars += chars;
+            }
+            Some("assistant") => {
+                stats.turn_count += 1;
+                stats.assistant_chars += chars;
+            }
+            _ => {}
+        }
+        stats.tool_call_count += msg_tools.len();
+        tools.extend(msg_tools);
+
+        if let Some(ts) = message_timestamp_ms(msg) {
+            stats.first_timestamp = Some(stats.first_timestamp.map_or(ts, |f| f.min(ts)));
+            stats.last_timestamp = Some(stats.last_timestamp.map_or(ts, |l| l.max(ts)));
+        }
+    }
+
+    stats.distinct_tools_used = tools.into_iter().collect();
+    stats.duration_span = stats.first_timestamp.zip(stats.last_timestamp).map(|(f, l)| l - f);
+    Ok(stats)
+}
+
+/// Full-text search over saved conversations: sessions whose message content
+/// contains `query` (case-insensitive), most recently active first.
+#[tauri::command]
+async fn search_sessions(query: String) -> Result<Vec<SessionIndex>, String> {
+    let needle = query.trim().to_lowercase();
+    if needle.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let entries = match std::fs::read_dir(sessions_dir()) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read sessions dir: {}", e)),
+    };
+
+    let mut matched = std::collections::HashSet::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") || path == sessions_index_path() {
+            continue;
+        }
+        let Ok(json) = std::fs::read_to_string(&path) else { continue };
+        let Ok(session) = serde_json::from_str::<SessionData>(&json) else { continue };
+
+        // Stops at the first matching message
+        let hit = session_messages(&session).iter().any(|msg| {
+            msg.get("content")
+                .map(|c| c.to_string().to_lowercase().contains(&needle))
+                .unwrap_or(false)
+        });
+        if hit {
+            matched.insert(session.id);
+        }
+    }
+
+    let mut results: Vec<SessionIndex> = list_sessions_internal()?
+        .into_iter()
+        .filter(|s| matched.contains(&s.id))
+        .collect();
+    results.sort_by(|a, b| b.last_activity.partial_cmp(&a.last_activity).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(results)
+}
+
 /// Delete a session file and remove from index.
 #[tauri::command]
-async fn delete_session_file(id: String) -> Result<(), String> {
+async fn delete_session_file(state: tauri::State<'_, AppState>, id: String) -> Result<(), String> {
     // Remove the data file
     let path = sessions_dir().join(format!("{}.json", id));
     if path.exists() {
-        std::fs::remove_file(&path)
+        remove_file_maybe_trash(&path, *state.soft_delete.lock().unwrap())
             .map_err(|e| format!("Failed to delete session file: {}", e))?;
     }
 
@@ -648,6 +2378,131 @@ async fn delete_session_file(id: String) -> Result<(), String> {
     write_sessions_index(&index)
 }
 
+/// Bulk-delete sessions whose last activity is older than `older_than_days`
+/// (None = any age), sparing pinned ones when `keep_pinned` is set.
+/// Returns how many were removed. Honors `soft_delete`.
+#[tauri::command]
+async fn prune_sessions(
+    state: tauri::State<'_, AppState>,
+    older_than_days: Option<u32>,
+    keep_pinned: bool,
+) -> Result<usize, String> {
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as f64;
+    // last_activity is Unix milliseconds (Date.now() on the frontend)
+    let cutoff = older_than_days.map(|days| now_ms - days as f64 * 86_400_000.0);
+
+    let (remove, mut keep): (Vec<SessionIndex>, Vec<SessionIndex>) = list_sessions_internal()?
+        .into_iter()
+        .partition(|s| {
+            !(keep_pinned && s.pinned) && cutoff.is_none_or(|c| s.last_activity < c)
+        });
+    if remove.is_empty() {
+        return Ok(0);
+    }
+
+    // A session whose file can't be deleted stays in the index
+    let soft = *state.soft_delete.lock().unwrap();
+    let mut removed = 0;
+    let mut first_error = None;
+    for session in remove {
+        let path = sessions_dir().join(format!("{}.json", session.id));
+        let result = if path.exists() { remove_file_maybe_trash(&path, soft) } else { Ok(()) };
+        match result {
+            Ok(()) => removed += 1,
+            Err(e) => {
+                first_error.get_or_insert(format!("Failed to delete session file: {}", e));
+                keep.push(session);
+            }
+        }
+    }
+    write_sessions_index(&keep)?;
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(removed),
+    }
+}
+
+/// Repair the index after a crash: re-add session files it doesn't list and
+/// drop entries whose file is gone. Returns the number of repairs made.
+#[tauri::command]
+async fn reconcile_sessions() -> Result<usize, String> {
+    let dir = sessions_dir();
+    let mut index = list_sessions_internal()?;
+    let before = index.len();
+
+    // Dangling entries: data file missing
+    index.retain(|s| dir.join(format!("{}.json", s.id)).exists());
+    let mut repairs = before - index.len();
+
+    // Orphaned files: written, but the index update never landed
+    let known: std::collections::HashSet<String> = index.iter().map(|s| s.id.clone()).collect();
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(format!("Failed to read sessions dir: {}", e)),
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file()
+            || path.extension().and_then(|e| e.to_str()) != Some("json")
+            || path == sessions_index_path()
+        {
+            continue;
+        }
+        let Ok(json) = std::fs::read_to_string(&path) else { continue };
+        let Ok(data) = serde_json::from_str::<SessionData>(&json) else {
+            eprintln!("Warning: Skipping unreadable session file {}", path.display());
+            continue;
+        };
+        if known.contains(&data.id) {
+            continue;
+        }
+        index.insert(0, SessionIndex {
+            id: data.id,
+            session_id: data.session_id,
+            title: data.title,
+            model: data.model,
+            message_count: data.message_count,
+            timestamp: data.timestamp,
+            last_activity: data.last_activity,
+            pinned: data.pinned,
+            readonly: data.readonly,
+        });
+        repairs += 1;
+    }
+
+    if repairs > 0 {
+        write_sessions_index(&index)?;
+    }
+    Ok(repairs)
+}
+
+/// Fork a conversation: copy its messages under a new id with " (copy)"
+/// appended to the title. The copy starts a fresh CLI resume chain and is
+/// unpinned. Returns the new id.
+#[tauri::command]
+async fn duplicate_session(id: String) -> Result<String, String> {
+    let mut session = load_session_internal(&id)?;
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as f64;
+
+    session.id = uuid::Uuid::new_v4().to_string();
+    session.session_id = None;
+    session.title = format!("{} (copy)", session.title);
+    session.timestamp = now_ms;
+    session.last_activity = now_ms;
+    session.pinned = false;
+
+    let new_id = session.id.clone();
+    save_session_file(session).await?;
+    Ok(new_id)
+}
+
 /// Update session title in the index (and the data file).
 #[tauri::command]
 async fn update_session_title(id: String, title: String) -> Result<(), String> {
@@ -716,21 +2571,220 @@ async fn migrate_sessions_from_localstorage(sessions: Vec<SessionData>) -> Resul
             let _ = std::fs::write(&file_path, json);
         }
 
-        // Add to index
-        index.push(SessionIndex {
-            id: session.id.clone(),
-            session_id: session.session_id.clone(),
-            title: session.title.clone(),
-            model: session.model.clone(),
-            message_count: session.message_count,
-            timestamp: session.timestamp,
-            last_activity: session.last_activity,
-            pinned: session.pinned,
-        });
+        // Add to index
+        index.push(SessionIndex {
+            id: session.id.clone(),
+            session_id: session.session_id.clone(),
+            title: session.title.clone(),
+            model: session.model.clone(),
+            message_count: session.message_count,
+            timestamp: session.timestamp,
+            last_activity: session.last_activity,
+            pinned: session.pinned,
+            readonly: session.readonly,
+        });
+    }
+
+    write_sessions_index(&index)?;
+    Ok(count)
+}
+
+fn session_archive_dir() -> PathBuf {
+    sessions_dir().join("archive")
+}
+
+/// Render messages as a plain "role: content" transcript for summarization.
+fn messages_transcript(messages: &[serde_json::Value]) -> String {
+    messages.iter()
+        .filter_map(|m| {
+            let role = m.get("role").and_then(|v| v.as_str()).unwrap_or("unknown");
+            let content = m.get("content").and_then(|v| v.as_str())?.trim();
+            if content.is_empty() { None } else { Some(format!("{}: {}", role, content)) }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Summarize all but the most recent `keep_recent` messages into a single
+/// system turn (a persisted equivalent of Claude's /compact). The summary comes
+/// from a tool-less single-turn query through `run_query`. The pre-compaction
+/// session is archived so `restore_compacted_session` can undo it. Clears the
+/// CLI `session_id`, since resuming the old chain would reload the full context.
+/// The query waits for a slot like any other; pass `query_id` to be able to
+/// cancel it with `cancel_query`. Returns the number of messages that were summarized.
+#[tauri::command]
+async fn compact_session(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    id: String,
+    keep_recent: usize,
+    query_id: Option<String>,
+) -> Result<usize, String> {
+    let mut session = load_session_internal(&id)?;
+    if session.readonly || session_is_readonly(&id) {
+        return Err("This session is read-only and can't be compacted".to_string());
+    }
+    let messages = session_messages(&session).to_vec();
+    if messages.len() <= keep_recent {
+        return Ok(0);
+    }
+    let split = messages.len() - keep_recent;
+    let (older, recent) = messages.split_at(split);
+
+    let transcript = messages_transcript(older);
+    if transcript.is_empty() {
+        return Err("Nothing to summarize in the older messages".to_string());
+    }
+
+    let config = QueryConfig {
+        message: format!(
+            "Summarize the following conversation so it can replace the original turns as context. \
+             Keep decisions, facts, file names, code identifiers and open questions. \
+             Reply with the summary only.\n\n{}",
+            transcript
+        ),
+        engine: Some("claude".to_string()),
+        max_turns: Some(1),
+        tools: Some(String::new()),
+        strict_mcp: true,
+        background: Some(true),
+        ..Default::default()
+    };
+
+    let query_id = query_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let slots = state.query_slots.lock().unwrap().clone();
+    let Some(_permit) = acquire_query_slot(&app, slots, state.queued_queries.clone(), &query_id).await else {
+        return Err("Compaction cancelled".to_string());
+    };
+
+    // Capture the final `result` text of the summarization query from its stream
+    let summary = std::sync::Arc::new(Mutex::new(String::new()));
+    let listener = {
+        let sink = summary.clone();
+        let qid = query_id.clone();
+        app.listen("claude-background-message", move |event| {
+            if let Ok(payload) = serde_json::from_str::<serde_json::Value>(event.payload()) {
+                if payload["queryId"].as_str() != Some(qid.as_str()) {
+                    return;
+                }
+                if let Some(Ok(msg)) = payload["data"].as_str().map(serde_json::from_str::<serde_json::Value>) {
+                    if msg["type"] == "result" {
+                        if let Some(text) = msg["result"].as_str() {
+                            *sink.lock().unwrap() = text.to_string();
+                        }
+                    }
+                }
+            }
+        })
+    };
+    let outcome = claude::run_query(&app, &query_id, config, state.processes.clone()).await;
+    app.unlisten(listener);
+    outcome?;
+
+    let summary = summary.lock().unwrap().trim().to_string();
+    if summary.is_empty() {
+        return Err("Summarization query returned no text".to_string());
+    }
+
+    // Archive the pre-compaction version before overwriting
+    let archive = session_archive_dir();
+    std::fs::create_dir_all(&archive)
+        .map_err(|e| format!("Failed to create archive dir: {}", e))?;
+    let original = serde_json::to_string(&session)
+        .map_err(|e| format!("Failed to serialize session: {}", e))?;
+    std::fs::write(archive.join(format!("{}.json", id)), original)
+        .map_err(|e| format!("Failed to archive session: {}", e))?;
+
+    let summary_turn = serde_json::json!({
+        "id": uuid::Uuid::new_v4().to_string(),
+        "role": "system",
+        "content": format!("[Summary of {} earlier messages]\n{}", split, summary),
+        "timestamp": older.first().and_then(|m| m.get("timestamp")).cloned()
+            .unwrap_or(serde_json::Value::from(session.timestamp)),
+    });
+    let mut compacted = vec![summary_turn];
+    compacted.extend_from_slice(recent);
+
+    session.message_count = compacted.len();
+    session.messages = serde_json::Value::Array(compacted);
+    session.session_id = None;
+    save_session_file(session).await?;
+
+    Ok(split)
+}
+
+/// Undo `compact_session` by restoring the archived pre-compaction version.
+#[tauri::command]
+async fn restore_compacted_session(id: String) -> Result<(), String> {
+    let path = session_archive_dir().join(format!("{}.json", id));
+    if !path.exists() {
+        return Err(format!("No archived version of session {}", id));
+    }
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read archived session: {}", e))?;
+    let session: SessionData = serde_json::from_str(&json)
+        .map_err(|e| format!("Failed to parse archived session: {}", e))?;
+    save_session_file(session).await?;
+    std::fs::remove_file(&path)
+        .map_err(|e| format!("Failed to remove archived session: {}", e))
+}
+
+const READONLY_BUNDLE_FORMAT: &str = "thunderclaude-readonly-session";
+
+/// Export a session as a self-contained JSON bundle for sharing: title, model,
+/// timestamps and messages only. The CLI `session_id` is stripped so the
+/// recipient can read the conversation but never resume it on your account.
+#[tauri::command]
+async fn export_session_readonly(id: String) -> Result<String, String> {
+    let session = load_session_internal(&id)?;
+    let bundle = serde_json::json!({
+        "format": READONLY_BUNDLE_FORMAT,
+        "version": 1,
+        "title": session.title,
+        "model": session.model,
+        "messageCount": session.message_count,
+        "timestamp": session.timestamp,
+        "lastActivity": session.last_activity,
+        "messages": session.messages,
+    });
+    serde_json::to_string_pretty(&bundle)
+        .map_err(|e| format!("Failed to serialize session bundle: {}", e))
+}
+
+/// Import a bundle produced by `export_session_readonly` as a new, read-only
+/// session. `send_query` refuses to continue read-only sessions.
+#[tauri::command]
+async fn import_readonly(bundle: String) -> Result<SessionIndex, String> {
+    let value: serde_json::Value = serde_json::from_str(&bundle)
+        .map_err(|e| format!("Failed to parse session bundle: {}", e))?;
+    if value.get("format").and_then(|f| f.as_str()) != Some(READONLY_BUNDLE_FORMAT) {
+        return Err("Not a ThunderClaude read-only session bundle".to_string());
     }
 
-    write_sessions_index(&index)?;
-    Ok(count)
+    let messages = value.get("messages").cloned().unwrap_or(serde_json::Value::Array(Vec::new()));
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as f64)
+        .unwrap_or(0.0);
+    let session = SessionData {
+        id: uuid::Uuid::new_v4().to_string(),
+        session_id: None,
+        title: value.get("title").and_then(|t| t.as_str()).unwrap_or("Shared session").to_string(),
+        model: value.get("model").and_then(|m| m.as_str()).unwrap_or_default().to_string(),
+        message_count: messages.as_array().map(|m| m.len()).unwrap_or(0),
+        timestamp: value.get("timestamp").and_then(|t| t.as_f64()).unwrap_or(now),
+        last_activity: value.get("lastActivity").and_then(|t| t.as_f64()).unwrap_or(now),
+        pinned: false,
+        readonly: true,
+        messages,
+    };
+    let id = session.id.clone();
+    save_session_file(session).await?;
+
+    list_sessions_internal()?
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| format!("Imported session {} missing from index", id))
 }
 
 #[tauri::command]
@@ -754,7 +2808,7 @@ async fn set_active_project(
     root_path: Option<String>,
 ) -> Result<(), String> {
     *state.active_project_id.lock().unwrap() = id;
-    *state.active_project_root.lock().unwrap() = root_path;
+    *state.active_project_root.lock().unwrap() = root_path.as_deref().map(expand_path).filter(|p| !p.is_empty());
     Ok(())
 }
 
@@ -764,20 +2818,14 @@ async fn save_projects(
     projects: Vec<ProjectConfig>,
     active_project_id: Option<String>,
 ) -> Result<(), String> {
-    *state.projects.lock().unwrap() = projects.clone();
-    *state.active_project_id.lock().unwrap() = active_project_id.clone();
-    let close_to_tray = *state.close_to_tray.lock().unwrap();
-    let vault_path = state.vault_path.lock().unwrap().clone();
-    save_settings_to_disk(&Settings {
-        close_to_tray,
-        vault_path,
-        projects,
-        active_project_id,
-    })
+    *state.projects.lock().unwrap() = projects;
+    *state.active_project_id.lock().unwrap() = active_project_id;
+    save_settings_to_disk(&current_settings(&state))
 }
 
 #[tauri::command]
 async fn validate_directory(path: String) -> Result<String, String> {
+    let path = expand_path(&path);
     let p = std::path::Path::new(&path);
     if !p.exists() {
         return Err(format!("Path does not exist: {}", path));
@@ -785,11 +2833,17 @@ async fn validate_directory(path: String) -> Result<String, String> {
     if !p.is_dir() {
         return Err(format!("Path is not a directory: {}", path));
     }
-    std::fs::canonicalize(p)
-        .map(|abs| abs.to_string_lossy().replace('\\', "/"))
+    dunce::canonicalize(p)
+        .map(|abs| slash_path(&abs))
         .map_err(|e| format!("Failed to resolve path: {}", e))
 }
 
+/// Path as returned to the frontend: forward slashes on every platform.
+/// Canonicalize with `dunce` first so Windows paths carry no `\\?\` prefix.
+fn slash_path(path: &std::path::Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
 // ── File system commands (for file tree + @ mentions) ────────────────────────
 
 #[derive(serde::Serialize)]
@@ -801,19 +2855,59 @@ struct DirEntry {
     extension: String,
 }
 
+/// With `restrict_filesystem` on, refuse paths that don't resolve inside the
+/// active project root, the vault, or ~/.thunderclaude. Paths that don't exist
+/// yet are judged by their nearest existing ancestor, so `..` is refused
+/// outright — it would otherwise be resolved only after that check.
+fn check_fs_access(state: &AppState, path: &str) -> Result<(), String> {
+    if !*state.restrict_filesystem.lock().unwrap() {
+        return Ok(());
+    }
+    let target = std::path::Path::new(path);
+    if target.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+        return Err(format!("Access denied for paths containing '..': {}", path));
+    }
+    let roots: Vec<PathBuf> = [
+        state.active_project_root.lock().unwrap().clone().map(PathBuf::from),
+        state.vault_path.lock().unwrap().clone().map(PathBuf::from),
+        Some(thunderclaude_dir()),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|root| std::fs::canonicalize(root).ok())
+    .collect();
+
+    let resolved = target
+        .ancestors()
+        .filter(|p| !p.as_os_str().is_empty())
+        .find(|p| p.exists())
+        .and_then(|p| std::fs::canonicalize(p).ok());
+    match resolved {
+        Some(real) if roots.iter().any(|root| real.starts_with(root)) => Ok(()),
+        _ => Err(format!("Access denied outside the project, vault and ThunderClaude folders: {}", path)),
+    }
+}
+
 #[tauri::command]
-async fn list_directory(path: String) -> Result<Vec<DirEntry>, String> {
+async fn list_directory(state: tauri::State<'_, AppState>, path: String) -> Result<Vec<DirEntry>, String> {
     // Normalize bare drive letters: "C:" → "C:\" (otherwise resolves to CWD on that drive)
     let path = if path.len() == 2 && path.ends_with(':') {
         format!("{}\\", path)
     } else {
-        path
+        expand_path(&path)
     };
+    check_fs_access(&state, &path)?;
     let dir = std::path::Path::new(&path);
     if !dir.exists() || !dir.is_dir() {
         return Err(format!("Not a valid directory: {}", path));
     }
+    // Canonical, so entry paths match what validate_directory returns
+    let dir = dunce::canonicalize(dir).map_err(|e| format!("Failed to resolve path: {}", e))?;
+    read_dir_sorted(&dir)
+}
 
+/// One level of `dir`: directories first, then alphabetically (case-insensitive).
+fn read_dir_sorted(dir: &std::path::Path) -> Result<Vec<DirEntry>, String> {
     let read_dir = std::fs::read_dir(dir)
         .map_err(|e| format!("Failed to read directory: {}", e))?;
 
@@ -831,7 +2925,7 @@ async fn list_directory(path: String) -> Result<Vec<DirEntry>, String> {
 
         entries.push(DirEntry {
             name,
-            path: entry.path().to_string_lossy().to_string(),
+            path: slash_path(&entry.path()),
             is_dir,
             size,
             extension,
@@ -848,21 +2942,195 @@ async fn list_directory(path: String) -> Result<Vec<DirEntry>, String> {
     Ok(entries)
 }
 
+/// Most entries `list_directory_tree` returns in one call.
+const MAX_TREE_ENTRIES: usize = 5000;
+
+#[derive(serde::Serialize)]
+struct TreeEntry {
+    #[serde(flatten)]
+    entry: DirEntry,
+    /// 1 for direct children of the requested path
+    depth: usize,
+    /// Path of the containing directory
+    parent: String,
+}
+
+#[derive(serde::Serialize)]
+struct DirectoryTree {
+    /// Depth-first, each level in `list_directory` order
+    entries: Vec<TreeEntry>,
+    /// Stopped at `MAX_TREE_ENTRIES`
+    truncated: bool,
+}
+
+/// Append `dir`'s entries (and, below `max_depth`, their children) to `out`.
+/// Returns false once the entry cap is hit.
+fn walk_tree(
+    dir: &std::path::Path,
+    depth: usize,
+    max_depth: usize,
+    ignored: &std::collections::HashSet<String>,
+    patterns: &ignore::gitignore::Gitignore,
+    out: &mut Vec<TreeEntry>,
+) -> bool {
+    let Ok(entries) = read_dir_sorted(dir) else { return true };
+    let parent = slash_path(dir);
+    for entry in entries {
+        if patterns.matched(&entry.path, entry.is_dir).is_ignore() {
+            continue;
+        }
+        if out.len() >= MAX_TREE_ENTRIES {
+            return false;
+        }
+        let descend = entry.is_dir && depth < max_depth && !ignored.contains(&entry.name);
+        let path = PathBuf::from(&entry.path);
+        out.push(TreeEntry { entry, depth, parent: parent.clone() });
+        if descend && !walk_tree(&path, depth + 1, max_depth, ignored, patterns, out) {
+            return false;
+        }
+    }
+    true
+}
+
+/// List `path` down to `max_depth` levels (1 = same as `list_directory`) as a
+/// flat, depth-first list for prefetching the file tree. Uses the same ignore
+/// rules as `search_files`: pattern-ignored entries are left out, ignored
+/// directory names are listed but not expanded.
+#[tauri::command]
+async fn list_directory_tree(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    max_depth: usize,
+) -> Result<DirectoryTree, String> {
+    check_fs_access(&state, &path)?;
+    let root = PathBuf::from(&path);
+    if !root.is_dir() {
+        return Err(format!("Not a valid directory: {}", path));
+    }
+    let root = dunce::canonicalize(&root).map_err(|e| format!("Failed to resolve path: {}", e))?;
+    let ignored = search_ignore_set(&state);
+    let patterns = search_ignore_patterns(&root, &[])?;
+
+    tokio::task::spawn_blocking(move || {
+        let mut entries = Vec::new();
+        let complete = walk_tree(&root, 1, max_depth.max(1), &ignored, &patterns, &mut entries);
+        DirectoryTree { entries, truncated: !complete }
+    })
+    .await
+    .map_err(|e| format!("Failed to list directory tree: {}", e))
+}
+
+/// Total size in bytes of the files under `path`, skipping the `search_files`
+/// ignore set. Symlinks are not followed, so link loops can't recurse forever.
+#[tauri::command]
+async fn directory_size(state: tauri::State<'_, AppState>, path: String) -> Result<u64, String> {
+    check_fs_access(&state, &path)?;
+    let root = PathBuf::from(&path);
+    if !root.is_dir() {
+        return Err(format!("Not a valid directory: {}", path));
+    }
+    let ignored = search_ignore_set(&state);
+    let patterns = search_ignore_patterns(&root, &[])?;
+
+    tokio::task::spawn_blocking(move || {
+        let mut total = 0u64;
+        let mut stack = vec![root];
+        while let Some(dir) = stack.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+            for entry in entries.flatten() {
+                // file_type() doesn't follow symlinks
+                let Ok(file_type) = entry.file_type() else { continue };
+                if file_type.is_symlink() || patterns.matched(entry.path(), file_type.is_dir()).is_ignore() {
+                    continue;
+                }
+                if file_type.is_dir() {
+                    if !ignored.contains(entry.file_name().to_string_lossy().as_ref()) {
+                        stack.push(entry.path());
+                    }
+                } else if let Ok(metadata) = entry.metadata() {
+                    total += metadata.len();
+                }
+            }
+        }
+        total
+    })
+    .await
+    .map_err(|e| format!("Failed to compute directory size: {}", e))
+}
+
+/// Directory names skipped by file search in every project.
+const DEFAULT_IGNORED_DIRS: &[&str] = &[
+    "node_modules", ".git", ".next", "dist", "build", "__pycache__",
+    ".cache", "target", ".turbo", ".vercel", ".svelte-kit", "coverage",
+];
+
+/// The active project's config, if one is selected.
+fn active_project(state: &AppState) -> Option<ProjectConfig> {
+    let active_id = state.active_project_id.lock().unwrap().clone()?;
+    state.projects.lock().unwrap().iter().find(|p| p.id == active_id).cloned()
+}
+
+/// Default ignore set merged with the active project's `search_ignored_dirs`.
+fn search_ignore_set(state: &AppState) -> std::collections::HashSet<String> {
+    let mut ignored: std::collections::HashSet<String> =
+        DEFAULT_IGNORED_DIRS.iter().map(|d| d.to_string()).collect();
+
+    let active_id = state.active_project_id.lock().unwrap().clone();
+    if let Some(id) = active_id {
+        let projects = state.projects.lock().unwrap();
+        if let Some(project) = projects.iter().find(|p| p.id == id) {
+            for dir in &project.search_ignored_dirs {
+                match dir.strip_prefix('!') {
+                    Some(included) => { ignored.remove(included); }
+                    None => { ignored.insert(dir.clone()); }
+                }
+            }
+        }
+    }
+    ignored
+}
+
+/// Gitignore-style matcher built from `<root>/.thunderclaudeignore` (if present)
+/// plus any extra patterns passed by the caller.
+fn search_ignore_patterns(
+    root: &std::path::Path,
+    extra: &[String],
+) -> Result<ignore::gitignore::Gitignore, String> {
+    let mut builder = ignore::gitignore::GitignoreBuilder::new(root);
+    let ignore_file = root.join(".thunderclaudeignore");
+    if ignore_file.is_file() {
+        if let Some(e) = builder.add(&ignore_file) {
+            eprintln!("Warning: Failed to parse {}: {}", ignore_file.display(), e);
+        }
+    }
+    for pattern in extra {
+        builder.add_line(None, pattern)
+            .map_err(|e| format!("Invalid ignore pattern '{}': {}", pattern, e))?;
+    }
+    builder.build().map_err(|e| format!("Failed to build ignore patterns: {}", e))
+}
+
 /// Recursive file search for @ mention autocomplete.
-/// Walks from `root`, skips ignored dirs, returns files matching `query` (case-insensitive substring).
+/// Walks from `root`, skips ignored dirs (defaults, project list, `.thunderclaudeignore`
+/// globs and `extra_ignores`), returns entries matching `query` (case-insensitive substring).
 /// Limited to 20 results for speed.
 #[tauri::command]
-async fn search_files(root: String, query: String) -> Result<Vec<DirEntry>, String> {
+async fn search_files(
+    state: tauri::State<'_, AppState>,
+    root: String,
+    query: String,
+    extra_ignores: Option<Vec<String>>,
+) -> Result<Vec<DirEntry>, String> {
+    check_fs_access(&state, &root)?;
     let root_path = std::path::Path::new(&root);
     if !root_path.exists() || !root_path.is_dir() {
         return Err(format!("Not a valid directory: {}", root));
     }
+    let root_path = &dunce::canonicalize(root_path).map_err(|e| format!("Failed to resolve path: {}", e))?;
 
     let query_lower = query.to_lowercase();
-    let ignored: std::collections::HashSet<&str> = [
-        "node_modules", ".git", ".next", "dist", "build", "__pycache__",
-        ".cache", "target", ".turbo", ".vercel", ".svelte-kit", "coverage",
-    ].into_iter().collect();
+    let ignored = search_ignore_set(&state);
+    let patterns = search_ignore_patterns(root_path, &extra_ignores.unwrap_or_default())?;
 
     let mut results: Vec<DirEntry> = Vec::new();
     let mut stack: Vec<std::path::PathBuf> = vec![root_path.to_path_buf()];
@@ -882,15 +3150,19 @@ async fn search_files(root: String, query: String) -> Result<Vec<DirEntry>, Stri
             let metadata = entry.metadata().ok();
             let is_dir = metadata.as_ref().map(|m| m.is_dir()).unwrap_or(false);
 
+            if patterns.matched(entry.path(), is_dir).is_ignore() {
+                continue;
+            }
+
             if is_dir {
-                if !ignored.contains(name.as_str()) {
+                if !ignored.contains(&name) {
                     stack.push(entry.path());
                 }
                 // Also match folder names
                 if name.to_lowercase().contains(&query_lower) {
                     results.push(DirEntry {
                         name: name.clone(),
-                        path: entry.path().to_string_lossy().to_string(),
+                        path: slash_path(&entry.path()),
                         is_dir: true,
                         size: 0,
                         extension: String::new(),
@@ -904,7 +3176,7 @@ async fn search_files(root: String, query: String) -> Result<Vec<DirEntry>, Stri
                     .unwrap_or_default();
                 results.push(DirEntry {
                     name,
-                    path: entry.path().to_string_lossy().to_string(),
+                    path: slash_path(&entry.path()),
                     is_dir: false,
                     size,
                     extension,
@@ -913,65 +3185,629 @@ async fn search_files(root: String, query: String) -> Result<Vec<DirEntry>, Stri
         }
     }
 
-    // Sort: exact prefix matches first, then by name
-    results.sort_by(|a, b| {
-        let a_starts = a.name.to_lowercase().starts_with(&query_lower);
-        let b_starts = b.name.to_lowercase().starts_with(&query_lower);
-        b_starts.cmp(&a_starts)
-            .then(a.is_dir.cmp(&b.is_dir).reverse())
-            .then(a.name.to_lowercase().cmp(&b.name.to_lowercase()))
-    });
+    // Sort: exact prefix matches first, then by name
+    results.sort_by(|a, b| {
+        let a_starts = a.name.to_lowercase().starts_with(&query_lower);
+        let b_starts = b.name.to_lowercase().starts_with(&query_lower);
+        b_starts.cmp(&a_starts)
+            .then(a.is_dir.cmp(&b.is_dir).reverse())
+            .then(a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+
+    Ok(results)
+}
+
+const MAX_CONTENT_MATCHES_PER_FILE: usize = 5;
+const MAX_CONTENT_SEARCH_FILE_BYTES: u64 = 2 * 1024 * 1024;
+const MAX_CONTENT_MATCH_LINE_CHARS: usize = 300;
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ContentMatch {
+    /// Path relative to the search root (forward slashes)
+    path: String,
+    /// 1-based line number
+    line_number: usize,
+    line: String,
+}
+
+/// Grep-style content search: case-insensitive substring match on every line
+/// of every text file under `root`, using the same ignore rules as
+/// `search_files`. Binary files (null byte in the first 8KB) and files over
+/// 2MB are skipped; at most 5 matches are returned per file.
+#[tauri::command]
+async fn search_file_contents(
+    state: tauri::State<'_, AppState>,
+    root: String,
+    query: String,
+    max_results: usize,
+    extra_ignores: Option<Vec<String>>,
+) -> Result<Vec<ContentMatch>, String> {
+    check_fs_access(&state, &root)?;
+    let root_path = std::path::PathBuf::from(&root);
+    if !root_path.is_dir() {
+        return Err(format!("Not a valid directory: {}", root));
+    }
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let query_lower = query.to_lowercase();
+    let ignored = search_ignore_set(&state);
+    let patterns = search_ignore_patterns(&root_path, &extra_ignores.unwrap_or_default())?;
+
+    tokio::task::spawn_blocking(move || {
+        let mut results: Vec<ContentMatch> = Vec::new();
+        let mut stack: Vec<std::path::PathBuf> = vec![root_path.clone()];
+
+        while let Some(dir) = stack.pop() {
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(e) => e,
+                Err(_) => continue,
+            };
+
+            for entry in entries.flatten() {
+                if results.len() >= max_results {
+                    return results;
+                }
+
+                let name = entry.file_name().to_string_lossy().to_string();
+                let metadata = match entry.metadata() {
+                    Ok(m) => m,
+                    Err(_) => continue,
+                };
+                let path = entry.path();
+                if patterns.matched(&path, metadata.is_dir()).is_ignore() {
+                    continue;
+                }
+                if metadata.is_dir() {
+                    if !ignored.contains(&name) {
+                        stack.push(path);
+                    }
+                    continue;
+                }
+                if metadata.len() > MAX_CONTENT_SEARCH_FILE_BYTES {
+                    continue;
+                }
+
+                let bytes = match std::fs::read(&path) {
+                    Ok(b) => b,
+                    Err(_) => continue,
+                };
+                if looks_binary(&bytes) {
+                    continue;
+                }
+                let text = String::from_utf8_lossy(&bytes);
+                let rel = path.strip_prefix(&root_path)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+
+                let mut file_matches = 0;
+                for (i, line) in text.lines().enumerate() {
+                    if !line.to_lowercase().contains(&query_lower) {
+                        continue;
+                    }
+                    results.push(ContentMatch {
+                        path: rel.clone(),
+                        line_number: i + 1,
+                        line: line.trim().chars().take(MAX_CONTENT_MATCH_LINE_CHARS).collect(),
+                    });
+                    file_matches += 1;
+                    if file_matches >= MAX_CONTENT_MATCHES_PER_FILE || results.len() >= max_results {
+                        break;
+                    }
+                }
+            }
+        }
+        results
+    })
+    .await
+    .map_err(|e| format!("Content search failed: {}", e))
+}
+
+#[tauri::command]
+async fn create_file(state: tauri::State<'_, AppState>, path: String, content: Option<String>) -> Result<(), String> {
+    check_fs_access(&state, &path)?;
+    let file = std::path::Path::new(&path);
+    if file.exists() {
+        return Err(format!("Already exists: {}", path));
+    }
+    if let Some(parent) = file.parent() {
+        if !parent.exists() {
+            return Err(format!("Parent directory does not exist: {}", parent.display()));
+        }
+    }
+    std::fs::write(&path, content.unwrap_or_default())
+        .map_err(|e| format!("Failed to create file: {}", e))
+}
+
+#[tauri::command]
+async fn create_directory(state: tauri::State<'_, AppState>, path: String) -> Result<(), String> {
+    check_fs_access(&state, &path)?;
+    let dir = std::path::Path::new(&path);
+    if dir.exists() {
+        return Err(format!("Already exists: {}", path));
+    }
+    if let Some(parent) = dir.parent() {
+        if !parent.exists() {
+            return Err(format!("Parent directory does not exist: {}", parent.display()));
+        }
+    }
+    std::fs::create_dir(&path).map_err(|e| format!("Failed to create directory: {}", e))
+}
+
+/// Move or rename a file or directory. `to` must not exist; its parent is
+/// created if needed. Falls back to copy + delete across filesystems.
+#[tauri::command]
+async fn rename_path(state: tauri::State<'_, AppState>, from: String, to: String) -> Result<(), String> {
+    check_fs_access(&state, &from)?;
+    check_fs_access(&state, &to)?;
+    let src = std::path::Path::new(&from);
+    let dst = std::path::Path::new(&to);
+    if !src.exists() {
+        return Err(format!("Not found: {}", from));
+    }
+    if dst.exists() {
+        return Err(format!("Already exists: {}", to));
+    }
+    if let Some(parent) = dst.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+    }
+
+    match std::fs::rename(src, dst) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            let copied = if src.is_dir() {
+                copy_dir_recursive(src, dst)
+            } else {
+                std::fs::copy(src, dst).map(|_| ())
+            };
+            if let Err(e) = copied {
+                // Don't leave a partial copy behind
+                let _ = if dst.is_dir() { std::fs::remove_dir_all(dst) } else { std::fs::remove_file(dst) };
+                return Err(format!("Failed to move {}: {}", from, e));
+            }
+            let removed = if src.is_dir() { std::fs::remove_dir_all(src) } else { std::fs::remove_file(src) };
+            removed.map_err(|e| format!("Copied to {} but failed to remove the original: {}", to, e))
+        }
+        Err(e) => Err(format!("Failed to rename {}: {}", from, e)),
+    }
+}
+
+/// Move a file or directory to the OS trash / recycle bin.
+#[tauri::command]
+async fn trash_path(state: tauri::State<'_, AppState>, path: String) -> Result<(), String> {
+    check_fs_access(&state, &path)?;
+    if !std::path::Path::new(&path).exists() {
+        return Err(format!("Not found: {}", path));
+    }
+    trash::delete(&path).map_err(|e| format!("Failed to move {} to trash: {}", path, e))
+}
+
+/// Remove a file, via the OS trash when `soft` (the `soft_delete` setting).
+fn remove_file_maybe_trash(path: &std::path::Path, soft: bool) -> Result<(), String> {
+    if soft {
+        trash::delete(path).map_err(|e| format!("Failed to move to trash: {}", e))
+    } else {
+        std::fs::remove_file(path).map_err(|e| e.to_string())
+    }
+}
+
+/// Delete a file, an empty directory, or (with `recursive`) a directory tree.
+/// Only paths strictly inside a configured project root are allowed, and
+/// never the active project root or one of its ancestors. A symlink is
+/// removed itself, never the directory it points to. Honors `soft_delete`.
+#[tauri::command]
+async fn delete_path(state: tauri::State<'_, AppState>, path: String, recursive: bool) -> Result<(), String> {
+    // Resolve only the parent so a symlink at `path` isn't followed
+    let given = std::path::Path::new(&path);
+    let name = given.file_name().ok_or_else(|| format!("Not a deletable path: {}", path))?;
+    let parent = given.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+    let target = std::fs::canonicalize(parent)
+        .map_err(|e| format!("Not found: {} ({})", path, e))?
+        .join(name);
+    let metadata = std::fs::symlink_metadata(&target).map_err(|e| format!("Not found: {} ({})", path, e))?;
+
+    let roots: Vec<PathBuf> = state.projects.lock().unwrap().iter()
+        .filter_map(|p| std::fs::canonicalize(&p.root_path).ok())
+        .collect();
+    if !roots.iter().any(|root| target.starts_with(root) && &target != root) {
+        return Err(format!("Refusing to delete outside a project root: {}", path));
+    }
+    let active_root = state.active_project_root.lock().unwrap().clone();
+    if let Some(active) = active_root.and_then(|r| std::fs::canonicalize(r).ok()) {
+        if active.starts_with(&target) {
+            return Err(format!("Refusing to delete the active project root or its parent: {}", path));
+        }
+    }
+
+    let soft = *state.soft_delete.lock().unwrap();
+    if !metadata.is_dir() {
+        return remove_file_maybe_trash(&target, soft).map_err(|e| format!("Failed to delete {}: {}", path, e));
+    }
+    if !recursive {
+        // remove_dir's emptiness rule, enforced before a move to the trash too
+        let mut entries = std::fs::read_dir(&target).map_err(|e| format!("Failed to delete {}: {}", path, e))?;
+        if entries.next().is_some() {
+            return Err(format!("Failed to delete {}: directory is not empty", path));
+        }
+    }
+    let result = if soft {
+        trash::delete(&target).map_err(|e| format!("Failed to move to trash: {}", e))
+    } else if recursive {
+        std::fs::remove_dir_all(&target).map_err(|e| e.to_string())
+    } else {
+        std::fs::remove_dir(&target).map_err(|e| e.to_string())
+    };
+    result.map_err(|e| format!("Failed to delete {}: {}", path, e))
+}
+
+const DEFAULT_MAX_FILE_READ_BYTES: u64 = 1024 * 1024;
+
+/// Binary heuristic: a null byte in the first 8KB.
+fn looks_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(8192)].contains(&0)
+}
+
+/// Read a whole text file, up to the `max_file_read_bytes` setting (use
+/// `read_file_range` for anything bigger).
+#[tauri::command]
+async fn read_file_content(state: tauri::State<'_, AppState>, path: String) -> Result<String, String> {
+    check_fs_access(&state, &path)?;
+    let file = std::path::Path::new(&path);
+    if !file.exists() {
+        return Err(format!("File not found: {}", path));
+    }
+    if file.is_dir() {
+        return Err("Cannot read directory as file".to_string());
+    }
+    let metadata =
+        std::fs::metadata(file).map_err(|e| format!("Failed to read metadata: {}", e))?;
+    let max_bytes = state.max_file_read_bytes.lock().unwrap().unwrap_or(DEFAULT_MAX_FILE_READ_BYTES);
+    if metadata.len() > max_bytes {
+        return Err(format!(
+            "File too large: {} bytes (max {} bytes)",
+            metadata.len(),
+            max_bytes
+        ));
+    }
+    let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+    if looks_binary(&bytes) {
+        return Err(format!("Cannot read binary file: {}", path));
+    }
+    String::from_utf8(bytes).map_err(|_| format!("Cannot read binary file (not valid UTF-8): {}", path))
+}
+
+/// A window of lines from a file, as returned by `read_file_range`.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileRange {
+    /// The requested lines, newline-joined (invalid UTF-8 replaced)
+    content: String,
+    /// 1-based line number of the first returned line
+    start_line: usize,
+    line_count: usize,
+    total_lines: usize,
+}
+
+/// Read lines `start_line..start_line + max_lines` (1-based) of a file of any
+/// size. The file is streamed line by line; only the window is kept in memory.
+#[tauri::command]
+async fn read_file_range(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    start_line: usize,
+    max_lines: usize,
+) -> Result<FileRange, String> {
+    use std::io::BufRead;
+    check_fs_access(&state, &path)?;
+    let file = std::path::Path::new(&path);
+    if !file.exists() {
+        return Err(format!("File not found: {}", path));
+    }
+    if file.is_dir() {
+        return Err("Cannot read directory as file".to_string());
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let handle = std::fs::File::open(&path).map_err(|e| format!("Failed to open file: {}", e))?;
+        let mut reader = std::io::BufReader::new(handle);
+        let start = start_line.max(1);
+        let mut lines = Vec::new();
+        let mut total_lines = 0;
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            let n = reader.read_until(b'\n', &mut buf)
+                .map_err(|e| format!("Failed to read file: {}", e))?;
+            if n == 0 {
+                break;
+            }
+            total_lines += 1;
+            if total_lines >= start && lines.len() < max_lines {
+                let line = String::from_utf8_lossy(&buf);
+                lines.push(line.trim_end_matches(['\n', '\r']).to_string());
+            }
+        }
+        Ok(FileRange {
+            content: lines.join("\n"),
+            start_line: start,
+            line_count: lines.len(),
+            total_lines,
+        })
+    })
+    .await
+    .map_err(|e| format!("Failed to read file: {}", e))?
+}
+
+// ── Saved slash commands (~/.thunderclaude/commands/<name>.md) ─────────────────
+
+fn commands_dir() -> PathBuf {
+    thunderclaude_dir().join("commands")
+}
+
+/// A saved prompt template. The file is optional `---` frontmatter
+/// (`description`, `model`, `engine`, `tools`, `permission_mode`) followed by
+/// the template body with `{placeholder}` slots.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SlashCommand {
+    name: String,
+    description: Option<String>,
+    model: Option<String>,
+    engine: Option<String>,
+    tools: Option<String>,
+    permission_mode: Option<String>,
+    /// Placeholder names found in the template, in order of first use
+    placeholders: Vec<String>,
+    template: String,
+}
+
+fn validate_command_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid command name '{}' (use letters, digits, - and _)", name));
+    }
+    Ok(())
+}
+
+/// Names of `{placeholder}` slots in a template (deduplicated, in order).
+fn template_placeholders(template: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end) => {
+                let name = &after[..end];
+                if !name.is_empty()
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    && !names.iter().any(|n| n == name)
+                {
+                    names.push(name.to_string());
+                }
+                rest = &after[end + 1..];
+            }
+            None => break,
+        }
+    }
+    names
+}
+
+/// Substitute `{name}` placeholders. Errors if any placeholder has no value.
+fn render_template(
+    template: &str,
+    args: &std::collections::HashMap<String, String>,
+) -> Result<String, String> {
+    let missing: Vec<String> = template_placeholders(template)
+        .into_iter()
+        .filter(|p| !args.contains_key(p))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Missing arguments: {}", missing.join(", ")));
+    }
+    // Single pass, so substituted values are never re-expanded
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').and_then(|end| args.get(&after[..end]).map(|v| (end, v))) {
+            Some((end, value)) => {
+                out.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn parse_slash_command(name: &str, raw: &str) -> SlashCommand {
+    let mut cmd = SlashCommand { name: name.to_string(), ..Default::default() };
+    let mut body = raw;
+
+    if let Some(after_open) = raw.strip_prefix("---") {
+        if let Some(end) = after_open.find("\n---") {
+            for line in after_open[..end].lines() {
+                let (key, value) = match line.split_once(':') {
+                    Some(kv) => kv,
+                    None => continue,
+                };
+                let value = value.trim().trim_matches('"').to_string();
+                let slot = match key.trim() {
+                    "description" => &mut cmd.description,
+                    "model" => &mut cmd.model,
+                    "engine" => &mut cmd.engine,
+                    "tools" => &mut cmd.tools,
+                    "permission_mode" => &mut cmd.permission_mode,
+                    _ => continue,
+                };
+                *slot = Some(value);
+            }
+            body = &after_open[end + 4..];
+        }
+    }
+
+    cmd.template = body.trim().to_string();
+    cmd.placeholders = template_placeholders(&cmd.template);
+    cmd
+}
 
-    Ok(results)
+fn load_slash_command(name: &str) -> Result<SlashCommand, String> {
+    validate_command_name(name)?;
+    let path = commands_dir().join(format!("{}.md", name));
+    let raw = std::fs::read_to_string(&path)
+        .map_err(|_| format!("Command not found: /{}", name))?;
+    Ok(parse_slash_command(name, &raw))
 }
 
+/// List saved slash commands, sorted by name.
 #[tauri::command]
-async fn create_file(path: String, content: Option<String>) -> Result<(), String> {
-    let file = std::path::Path::new(&path);
-    if file.exists() {
-        return Err(format!("Already exists: {}", path));
+async fn list_commands() -> Result<Vec<SlashCommand>, String> {
+    let dir = commands_dir();
+    if !dir.is_dir() {
+        return Ok(Vec::new());
     }
-    if let Some(parent) = file.parent() {
-        if !parent.exists() {
-            return Err(format!("Parent directory does not exist: {}", parent.display()));
+    let read_dir = std::fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to read commands dir: {}", e))?;
+
+    let mut commands: Vec<SlashCommand> = Vec::new();
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.extension().map(|e| e != "md").unwrap_or(true) {
+            continue;
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        if let Ok(raw) = std::fs::read_to_string(&path) {
+            commands.push(parse_slash_command(&name, &raw));
         }
     }
-    std::fs::write(&path, content.unwrap_or_default())
-        .map_err(|e| format!("Failed to create file: {}", e))
+    commands.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(commands)
 }
 
+/// Register (create or overwrite) a slash command from its raw markdown.
 #[tauri::command]
-async fn create_directory(path: String) -> Result<(), String> {
-    let dir = std::path::Path::new(&path);
-    if dir.exists() {
-        return Err(format!("Already exists: {}", path));
+async fn save_command(name: String, content: String) -> Result<(), String> {
+    validate_command_name(&name)?;
+    let dir = commands_dir();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create commands dir: {}", e))?;
+    std::fs::write(dir.join(format!("{}.md", name)), content)
+        .map_err(|e| format!("Failed to write command: {}", e))
+}
+
+#[tauri::command]
+async fn delete_command(name: String) -> Result<(), String> {
+    validate_command_name(&name)?;
+    let path = commands_dir().join(format!("{}.md", name));
+    if path.exists() {
+        std::fs::remove_file(&path)
+            .map_err(|e| format!("Failed to delete command: {}", e))?;
     }
-    if let Some(parent) = dir.parent() {
-        if !parent.exists() {
-            return Err(format!("Parent directory does not exist: {}", parent.display()));
+    Ok(())
+}
+
+/// Render a saved command with `args` and send it as a query, applying the
+/// model/engine/tools/permission mode from its frontmatter. Returns the query ID.
+#[tauri::command]
+async fn run_command(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    name: String,
+    args: std::collections::HashMap<String, String>,
+) -> Result<String, String> {
+    let command = load_slash_command(&name)?;
+    let message = render_template(&command.template, &args)?;
+    let config = QueryConfig {
+        message,
+        model: command.model,
+        engine: command.engine,
+        tools: command.tools,
+        permission_mode: command.permission_mode,
+        ..Default::default()
+    };
+    send_query(app, state, config).await
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EditPreview {
+    /// Unified diff (3 lines of context); empty when the content is unchanged
+    diff: String,
+    is_new_file: bool,
+    lines_added: usize,
+    lines_removed: usize,
+}
+
+/// Compute a unified diff between a file on disk and proposed new content.
+fn compute_edit_preview(path: &str, new_content: &str) -> Result<EditPreview, String> {
+    let file = std::path::Path::new(path);
+    let is_new_file = !file.exists();
+    let old_content = if is_new_file {
+        String::new()
+    } else {
+        std::fs::read_to_string(file).map_err(|e| format!("Failed to read file: {}", e))?
+    };
+
+    let diff = similar::TextDiff::from_lines(old_content.as_str(), new_content);
+    let (mut lines_added, mut lines_removed) = (0, 0);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            similar::ChangeTag::Insert => lines_added += 1,
+            similar::ChangeTag::Delete => lines_removed += 1,
+            similar::ChangeTag::Equal => {}
         }
     }
-    std::fs::create_dir(&path).map_err(|e| format!("Failed to create directory: {}", e))
+    let unified = diff
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string();
+
+    Ok(EditPreview { diff: unified, is_new_file, lines_added, lines_removed })
 }
 
+/// Preview a proposed edit as a unified diff. When `query_id` is given, the
+/// proposal is held for review until `apply_or_reject_edit` decides on it.
 #[tauri::command]
-async fn read_file_content(path: String) -> Result<String, String> {
-    let file = std::path::Path::new(&path);
-    if !file.exists() {
-        return Err(format!("File not found: {}", path));
-    }
-    if file.is_dir() {
-        return Err("Cannot read directory as file".to_string());
+async fn preview_edit(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    new_content: String,
+    query_id: Option<String>,
+) -> Result<EditPreview, String> {
+    check_fs_access(&state, &path)?;
+    let preview = compute_edit_preview(&path, &new_content)?;
+    if let Some(qid) = query_id {
+        state.pending_edits.lock().unwrap().insert((qid, path), new_content);
     }
-    let metadata =
-        std::fs::metadata(file).map_err(|e| format!("Failed to read metadata: {}", e))?;
-    if metadata.len() > 1024 * 1024 {
-        return Err(format!(
-            "File too large: {} bytes (max 1MB)",
-            metadata.len()
-        ));
+    Ok(preview)
+}
+
+/// Accept (write to disk) or reject (discard) a proposed edit registered via
+/// `preview_edit`. Returns true if the file was written.
+#[tauri::command]
+async fn apply_or_reject_edit(
+    state: tauri::State<'_, AppState>,
+    query_id: String,
+    path: String,
+    accept: bool,
+) -> Result<bool, String> {
+    let content = state.pending_edits.lock().unwrap().remove(&(query_id, path.clone()))
+        .ok_or_else(|| format!("No pending edit for {}", path))?;
+    if !accept {
+        return Ok(false);
     }
-    std::fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))
+    check_fs_access(&state, &path)?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to apply edit: {}", e))?;
+    Ok(true)
 }
 
 // ── Cost analytics persistence ──────────────────────────────────────────────
@@ -980,6 +3816,34 @@ fn analytics_path() -> PathBuf {
     thunderclaude_dir().join("analytics.json")
 }
 
+/// The live log is rotated to analytics.1.json once it reaches this size.
+const ANALYTICS_ROTATE_BYTES: u64 = 5 * 1024 * 1024;
+/// Rotated files kept (analytics.1.json is the newest); older ones are deleted.
+const ANALYTICS_ROTATED_FILES: usize = 3;
+
+/// `n`-th rotated log (1 = newest).
+fn rotated_analytics_path(n: usize) -> PathBuf {
+    thunderclaude_dir().join(format!("analytics.{}.json", n))
+}
+
+/// Shift analytics.json → .1 → .2 … when the live log is over the size cap.
+fn rotate_analytics_if_needed() -> Result<(), String> {
+    let path = analytics_path();
+    let len = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    if len < ANALYTICS_ROTATE_BYTES {
+        return Ok(());
+    }
+    for n in (1..ANALYTICS_ROTATED_FILES).rev() {
+        let from = rotated_analytics_path(n);
+        if from.exists() {
+            std::fs::rename(&from, rotated_analytics_path(n + 1))
+                .map_err(|e| format!("Failed to rotate analytics: {}", e))?;
+        }
+    }
+    std::fs::rename(&path, rotated_analytics_path(1))
+        .map_err(|e| format!("Failed to rotate analytics: {}", e))
+}
+
 /// Append a cost entry to the analytics log. Each entry is a JSON object on one line.
 #[tauri::command]
 async fn append_analytics(entry_json: String) -> Result<(), String> {
@@ -987,6 +3851,7 @@ async fn append_analytics(entry_json: String) -> Result<(), String> {
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
     }
+    rotate_analytics_if_needed()?;
     use std::io::Write;
     let mut file = std::fs::OpenOptions::new()
         .create(true)
@@ -998,44 +3863,481 @@ async fn append_analytics(entry_json: String) -> Result<(), String> {
     Ok(())
 }
 
-/// Read all analytics entries (newline-delimited JSON).
+/// Read all analytics entries (newline-delimited JSON), oldest rotated file first.
 #[tauri::command]
 async fn load_analytics() -> Result<String, String> {
+    let paths = (1..=ANALYTICS_ROTATED_FILES)
+        .rev()
+        .map(rotated_analytics_path)
+        .chain(std::iter::once(analytics_path()));
+
+    let mut all = String::new();
+    for path in paths.filter(|p| p.exists()) {
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read analytics: {}", e))?;
+        all.push_str(&text);
+        if !all.is_empty() && !all.ends_with('\n') {
+            all.push('\n');
+        }
+    }
+    Ok(all)
+}
+
+/// One line of analytics.json, as written by the frontend cost tracker.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CostEntry {
+    /// ISO 8601 timestamp
+    ts: String,
+    #[serde(default)]
+    cost: f64,
+    #[serde(default)]
+    tokens_in: u64,
+    #[serde(default)]
+    tokens_out: u64,
+    #[serde(default)]
+    model: String,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AnalyticsTotals {
+    total_cost: f64,
+    tokens_in: u64,
+    tokens_out: u64,
+    query_count: usize,
+}
+
+impl AnalyticsTotals {
+    fn add(&mut self, entry: &CostEntry) {
+        self.total_cost += entry.cost;
+        self.tokens_in += entry.tokens_in;
+        self.tokens_out += entry.tokens_out;
+        self.query_count += 1;
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AnalyticsSummary {
+    totals: AnalyticsTotals,
+    by_model: std::collections::BTreeMap<String, AnalyticsTotals>,
+    /// Keyed by local date (YYYY-MM-DD)
+    by_day: std::collections::BTreeMap<String, AnalyticsTotals>,
+    /// Malformed lines that were ignored
+    skipped: usize,
+}
+
+/// Aggregate the analytics log server-side: totals by model and by local day,
+/// optionally limited to `[from, to]` (Unix milliseconds). Malformed lines are skipped.
+#[tauri::command]
+async fn get_analytics_summary(from: Option<f64>, to: Option<f64>) -> Result<AnalyticsSummary, String> {
+    let raw = load_analytics().await?;
+    let mut summary = AnalyticsSummary {
+        totals: AnalyticsTotals::default(),
+        by_model: std::collections::BTreeMap::new(),
+        by_day: std::collections::BTreeMap::new(),
+        skipped: 0,
+    };
+
+    for line in raw.lines().filter(|l| !l.trim().is_empty()) {
+        let parsed = serde_json::from_str::<CostEntry>(line).ok().and_then(|entry| {
+            let ts = chrono::DateTime::parse_from_rfc3339(&entry.ts).ok()?;
+            Some((entry, ts))
+        });
+        let (entry, ts) = match parsed {
+            Some(p) => p,
+            None => {
+                summary.skipped += 1;
+                continue;
+            }
+        };
+
+        let millis = ts.timestamp_millis() as f64;
+        if from.map(|f| millis < f).unwrap_or(false) || to.map(|t| millis > t).unwrap_or(false) {
+            continue;
+        }
+
+        let day = ts.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string();
+        summary.totals.add(&entry);
+        summary.by_model.entry(entry.model.clone()).or_default().add(&entry);
+        summary.by_day.entry(day).or_default().add(&entry);
+    }
+
+    Ok(summary)
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AnalyticsTail {
+    /// Complete newline-delimited entries appended since the requested offset
+    data: String,
+    /// Offset to pass to the next call
+    offset: u64,
+    /// True when the log shrank (rotated/truncated) and `data` starts from the beginning
+    reset: bool,
+}
+
+/// Return only the complete lines appended after `since_offset`, so a live
+/// dashboard can poll deltas instead of re-reading the whole log.
+#[tauri::command]
+async fn tail_analytics(since_offset: u64) -> Result<AnalyticsTail, String> {
+    use std::io::{Read, Seek, SeekFrom};
     let path = analytics_path();
     if !path.exists() {
-        return Ok(String::new());
+        return Ok(AnalyticsTail { data: String::new(), offset: 0, reset: since_offset > 0 });
     }
-    std::fs::read_to_string(&path).map_err(|e| format!("Failed to read analytics: {}", e))
+
+    let mut file = std::fs::File::open(&path)
+        .map_err(|e| format!("Failed to open analytics: {}", e))?;
+    let len = file.metadata().map_err(|e| format!("Failed to read metadata: {}", e))?.len();
+
+    let reset = len < since_offset;
+    let start = if reset { 0 } else { since_offset };
+    file.seek(SeekFrom::Start(start))
+        .map_err(|e| format!("Failed to seek analytics: {}", e))?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)
+        .map_err(|e| format!("Failed to read analytics: {}", e))?;
+
+    // Hold back a trailing partial line until its newline is written
+    let complete = buf.iter().rposition(|&b| b == b'\n').map(|i| i + 1).unwrap_or(0);
+    buf.truncate(complete);
+
+    Ok(AnalyticsTail {
+        data: String::from_utf8_lossy(&buf).into_owned(),
+        offset: start + complete as u64,
+        reset,
+    })
 }
 
 // ── Temp image storage (for vision/image input) ─────────────────────────────
 
+fn temp_images_dir() -> PathBuf {
+    std::env::temp_dir().join("thunderclaude-images")
+}
+
+/// Temp images older than this are deleted at startup.
+const TEMP_IMAGE_MAX_AGE_HOURS: u64 = 6;
+const DEFAULT_MAX_TEMP_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+const DEFAULT_MAX_TEMP_IMAGES_TOTAL_BYTES: u64 = 200 * 1024 * 1024;
+
 /// Save base64-encoded image data to a temp file. Returns the absolute path.
 /// Used by the frontend to pass images to CLI processes via file path references.
+/// The extension comes from the image's magic bytes, not `name`; anything that
+/// isn't png/jpeg/gif/webp is rejected, as is anything over the
+/// `max_temp_image_bytes` setting. Oldest images are evicted to keep the
+/// folder under `max_temp_images_total_bytes`.
 #[tauri::command]
-async fn save_temp_image(name: String, base64_data: String) -> Result<String, String> {
+async fn save_temp_image(
+    state: tauri::State<'_, AppState>,
+    name: String,
+    base64_data: String,
+) -> Result<String, String> {
     use base64::Engine as _;
+    let max_bytes = state.max_temp_image_bytes.lock().unwrap().unwrap_or(DEFAULT_MAX_TEMP_IMAGE_BYTES);
+    let total_cap = state.max_temp_images_total_bytes.lock().unwrap()
+        .unwrap_or(DEFAULT_MAX_TEMP_IMAGES_TOTAL_BYTES);
+    // Decoded size, checked before decoding so oversized input is never buffered twice
+    let decoded_len = (base64_data.trim_end_matches('=').len() as u64) * 3 / 4;
+    if decoded_len > max_bytes {
+        return Err(format!("Image too large: {} bytes (max {} bytes)", decoded_len, max_bytes));
+    }
     let bytes = base64::engine::general_purpose::STANDARD
         .decode(&base64_data)
         .map_err(|e| format!("base64 decode failed: {}", e))?;
+    let mime_type = sniff_image_mime(&bytes)
+        .ok_or("Not a supported image (png/jpeg/gif/webp)")?;
+    let extension = match mime_type {
+        "image/jpeg" => "jpg",
+        other => other.trim_start_matches("image/"),
+    };
 
-    let dir = std::env::temp_dir().join("thunderclaude-images");
+    let dir = temp_images_dir();
     std::fs::create_dir_all(&dir)
         .map_err(|e| format!("Failed to create temp image dir: {}", e))?;
 
-    let filename = format!("{}_{}", uuid::Uuid::new_v4(), name);
+    // Keep only the caller's base name, minus any extension or directories
+    let stem = std::path::Path::new(&name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let filename = if stem.is_empty() {
+        format!("{}.{}", uuid::Uuid::new_v4(), extension)
+    } else {
+        format!("{}_{}.{}", uuid::Uuid::new_v4(), stem, extension)
+    };
     let path = dir.join(&filename);
     std::fs::write(&path, &bytes)
         .map_err(|e| format!("Failed to write temp image: {}", e))?;
 
+    evict_temp_images(total_cap, &path);
+
     Ok(path.to_string_lossy().to_string())
 }
 
+/// Delete the oldest temp images until the folder totals at most `cap` bytes.
+/// `keep` (the image just saved) is never evicted.
+fn evict_temp_images(cap: u64, keep: &std::path::Path) {
+    let Ok(entries) = std::fs::read_dir(temp_images_dir()) else { return };
+    let mut images: Vec<(std::time::SystemTime, u64, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+            Some((modified, metadata.len(), entry.path()))
+        })
+        .collect();
+    let mut total: u64 = images.iter().map(|(_, size, _)| size).sum();
+    images.sort_by_key(|(modified, _, _)| *modified);
+    for (_, size, path) in images {
+        if total <= cap {
+            break;
+        }
+        if path != keep && std::fs::remove_file(&path).is_ok() {
+            total -= size;
+        }
+    }
+}
+
+/// Delete temp images last modified before `max_age` ago (all of them for
+/// zero). Returns how many were removed.
+fn prune_temp_images(max_age: std::time::Duration) -> Result<usize, String> {
+    let entries = match std::fs::read_dir(temp_images_dir()) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(format!("Failed to read temp image dir: {}", e)),
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else { continue };
+        let age = metadata.modified().ok().and_then(|t| t.elapsed().ok()).unwrap_or_default();
+        if metadata.is_file() && age >= max_age && std::fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Delete saved temp images: those older than `older_than_hours`, or all of
+/// them when unset. Returns how many were removed.
+#[tauri::command]
+async fn clear_temp_images(older_than_hours: Option<u64>) -> Result<usize, String> {
+    let max_age = std::time::Duration::from_secs(older_than_hours.unwrap_or(0) * 3600);
+    tokio::task::spawn_blocking(move || prune_temp_images(max_age))
+        .await
+        .map_err(|e| format!("Failed to clear temp images: {}", e))?
+}
+
+/// Detect an image MIME type from its magic bytes.
+fn sniff_image_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageData {
+    base64_data: String,
+    mime_type: String,
+}
+
+/// Read an image file back as base64 so a resumed session can re-register it
+/// via `save_temp_image` after the original temp file was cleaned up.
+#[tauri::command]
+async fn read_image_as_base64(path: String, max_bytes: Option<u64>) -> Result<ImageData, String> {
+    use base64::Engine as _;
+    let file = std::path::Path::new(&path);
+    if !file.is_file() {
+        return Err(format!("Image not found: {}", path));
+    }
+    let limit = max_bytes.unwrap_or(20 * 1024 * 1024);
+    let metadata =
+        std::fs::metadata(file).map_err(|e| format!("Failed to read metadata: {}", e))?;
+    if metadata.len() > limit {
+        return Err(format!(
+            "Image too large: {} bytes (max {} bytes)",
+            metadata.len(),
+            limit
+        ));
+    }
+
+    let bytes = std::fs::read(file).map_err(|e| format!("Failed to read image: {}", e))?;
+    let mime_type = sniff_image_mime(&bytes)
+        .ok_or_else(|| format!("Not a supported image (png/jpeg/gif/webp): {}", path))?;
+
+    Ok(ImageData {
+        base64_data: base64::engine::general_purpose::STANDARD.encode(&bytes),
+        mime_type: mime_type.to_string(),
+    })
+}
+
+// ── Background vault reindexing ─────────────────────────────────────────────
+
+const DEFAULT_AUTO_REINDEX_MINS: u64 = 30;
+
+/// (Re)start the reindex scheduler from the current interval setting.
+/// Aborts any running scheduler; leaves it stopped when the interval is unset.
+fn restart_auto_reindex(app: &tauri::AppHandle, state: &AppState) {
+    let mut task = state.auto_reindex_task.lock().unwrap();
+    if let Some(handle) = task.take() {
+        handle.abort();
+    }
+    let mins = match *state.auto_reindex_interval_mins.lock().unwrap() {
+        Some(m) if m > 0 => m,
+        _ => return,
+    };
+
+    let app = app.clone();
+    *task = Some(tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(mins * 60));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        interval.tick().await; // the first tick completes immediately
+        loop {
+            interval.tick().await;
+            run_auto_reindex(&app).await;
+        }
+    }));
+}
+
+/// One scheduled scan→diff→embed pass over the configured vault. Skipped when
+/// no vault is set or another index run is in progress. Emits
+/// `auto-reindex-progress` ({ done, total } files), then `auto-reindex-done`
+/// (summary) or `auto-reindex-error`.
+async fn run_auto_reindex(app: &tauri::AppHandle) {
+    let root = match vault_root(&app.state::<AppState>()) {
+        Ok(r) => r,
+        Err(_) => return,
+    };
+    let search_state = app.state::<search::SearchState>();
+    let _guard = match search_state.try_begin_indexing() {
+        Some(g) => g,
+        None => return,
+    };
+
+    let result = async {
+        search::init_model(app, &search_state, None).await?;
+
+        let scan_root = root.clone();
+        let files = tokio::task::spawn_blocking(move || {
+            let mut files = Vec::new();
+            walk_vault(&scan_root, |f| files.push((f.path, f.modified)));
+            files
+        })
+        .await
+        .map_err(|e| format!("Vault scan failed: {}", e))?;
+
+        let progress_app = app.clone();
+        search::reindex_vault(&search_state, &root, files, move |done, total| {
+            let _ = progress_app.emit(
+                "auto-reindex-progress",
+                serde_json::json!({ "done": done, "total": total }),
+            );
+        })
+        .await
+    }
+    .await;
+
+    match result {
+        Ok(summary) => { let _ = app.emit("auto-reindex-done", summary); }
+        Err(e) => { let _ = app.emit("auto-reindex-error", e); }
+    }
+}
+
+/// Enable the background reindex scheduler (optionally changing its interval)
+/// and persist the setting. Returns the interval in effect.
+#[tauri::command]
+async fn start_auto_reindex(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    interval_mins: Option<u64>,
+) -> Result<u64, String> {
+    let mins = interval_mins
+        .or(*state.auto_reindex_interval_mins.lock().unwrap())
+        .unwrap_or(DEFAULT_AUTO_REINDEX_MINS);
+    if mins == 0 {
+        return Err("Reindex interval must be at least 1 minute".to_string());
+    }
+    *state.auto_reindex_interval_mins.lock().unwrap() = Some(mins);
+    restart_auto_reindex(&app, &state);
+    save_settings_to_disk(&current_settings(&state))?;
+    Ok(mins)
+}
+
+/// Stop the background reindex scheduler and persist it as disabled.
+/// A pass already underway is cancelled.
+#[tauri::command]
+async fn stop_auto_reindex(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    *state.auto_reindex_interval_mins.lock().unwrap() = None;
+    restart_auto_reindex(&app, &state);
+    save_settings_to_disk(&current_settings(&state))
+}
+
+// ── Startup warm-up ─────────────────────────────────────────────────────────
+
+/// Pre-resolve CLI binaries, load the sessions index, and (when a vault is
+/// configured and auto-index is on) initialize the embedding model, so the
+/// first user action doesn't pay discovery/load costs. Also prunes stale temp
+/// images. Emits `warmup-complete`.
+async fn warm_up(app: tauri::AppHandle) {
+    let (claude_binary, gemini_binary, session_count) = tokio::task::spawn_blocking(|| {
+        if let Err(e) = prune_temp_images(std::time::Duration::from_secs(TEMP_IMAGE_MAX_AGE_HOURS * 3600)) {
+            eprintln!("Warm-up: temp image cleanup failed: {}", e);
+        }
+        let claude_binary = claude::check_claude_available();
+        let gemini_binary = claude::check_gemini_available();
+        let session_count = list_sessions_internal().map(|s| s.len()).unwrap_or(0);
+        (claude_binary, gemini_binary, session_count)
+    })
+    .await
+    .unwrap_or_default();
+
+    let should_index = {
+        let state = app.state::<AppState>();
+        let vault_configured = state.vault_path.lock().unwrap().is_some();
+        vault_configured && *state.auto_index.lock().unwrap()
+    };
+
+    let mut embedding_initialized = false;
+    if should_index {
+        match search::init_model(&app, &app.state::<search::SearchState>(), None).await {
+            Ok(status) => embedding_initialized = status.initialized,
+            Err(e) => eprintln!("Warm-up: embedding init failed: {}", e),
+        }
+    }
+
+    let _ = app.emit(
+        "warmup-complete",
+        serde_json::json!({
+            "claudeBinary": claude_binary,
+            "geminiBinary": gemini_binary,
+            "sessionCount": session_count,
+            "embeddingInitialized": embedding_initialized,
+        }),
+    );
+}
+
 // ── Main entry point ────────────────────────────────────────────────────────
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let initial_settings = load_settings_from_disk();
+    claude::set_binary_overrides(
+        initial_settings.claude_binary_path.clone(),
+        initial_settings.gemini_binary_path.clone(),
+    );
 
     tauri::Builder::default()
         .plugin(tauri_plugin_updater::Builder::new().build())
@@ -1047,7 +4349,15 @@ pub fn run() {
         ))
         .manage(AppState {
             close_to_tray: Mutex::new(initial_settings.close_to_tray),
+            tray_available: Mutex::new(false),
             vault_path: Mutex::new(initial_settings.vault_path.clone()),
+            auto_index: Mutex::new(initial_settings.auto_index),
+            embedding_batch_size: Mutex::new(initial_settings.embedding_batch_size),
+            embedding_tokenizer_threads: Mutex::new(initial_settings.embedding_tokenizer_threads),
+            auto_reindex_interval_mins: Mutex::new(initial_settings.auto_reindex_interval_mins),
+            auto_reindex_task: Mutex::new(None),
+            claude_binary_path: Mutex::new(initial_settings.claude_binary_path.clone()),
+            gemini_binary_path: Mutex::new(initial_settings.gemini_binary_path.clone()),
             active_project_root: Mutex::new(
                 initial_settings.active_project_id.as_ref().and_then(|id| {
                     initial_settings.projects.iter()
@@ -1058,16 +4368,32 @@ pub fn run() {
             projects: Mutex::new(initial_settings.projects),
             active_project_id: Mutex::new(initial_settings.active_project_id),
             processes: std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new())),
+            max_concurrent_queries: Mutex::new(initial_settings.max_concurrent_queries),
+            max_file_read_bytes: Mutex::new(initial_settings.max_file_read_bytes),
+            soft_delete: Mutex::new(initial_settings.soft_delete),
+            restrict_filesystem: Mutex::new(initial_settings.restrict_filesystem),
+            max_temp_image_bytes: Mutex::new(initial_settings.max_temp_image_bytes),
+            max_temp_images_total_bytes: Mutex::new(initial_settings.max_temp_images_total_bytes),
+            query_slots: Mutex::new(query_semaphore(initial_settings.max_concurrent_queries)),
+            queued_queries: std::sync::Arc::default(),
+            pending_edits: Mutex::new(std::collections::HashMap::new()),
+        })
+        .manage({
+            let search_state = search::SearchState::new();
+            search_state.set_embedding_options(
+                initial_settings.embedding_batch_size,
+                initial_settings.embedding_tokenizer_threads,
+            );
+            search_state
         })
-        .manage(search::SearchState::new())
         .setup(|app| {
             // Build tray context menu
             let show = MenuItem::with_id(app, "show", "Show ThunderClaude", true, None::<&str>)?;
             let quit = MenuItem::with_id(app, "quit", "Quit ThunderClaude", true, None::<&str>)?;
             let menu = Menu::with_items(app, &[&show, &quit])?;
 
-            // Build tray icon
-            TrayIconBuilder::new()
+            // Build tray icon (not fatal: without it, closing the window quits instead)
+            let tray = TrayIconBuilder::new()
                 .icon(app.default_window_icon().unwrap().clone())
                 .tooltip("ThunderClaude")
                 .menu(&menu)
@@ -1099,7 +4425,14 @@ pub fn run() {
                         }
                     }
                 })
-                .build(app)?;
+                .build(app);
+            match tray {
+                Ok(_) => *app.state::<AppState>().tray_available.lock().unwrap() = true,
+                Err(e) => eprintln!("Failed to build tray icon: {}", e),
+            }
+
+            tauri::async_runtime::spawn(warm_up(app.handle().clone()));
+            restart_auto_reindex(app.handle(), &app.state::<AppState>());
 
             Ok(())
         })
@@ -1107,7 +4440,7 @@ pub fn run() {
             if let tauri::WindowEvent::CloseRequested { api, .. } = event {
                 let app = window.app_handle();
                 let state = app.state::<AppState>();
-                if *state.close_to_tray.lock().unwrap() {
+                if *state.close_to_tray.lock().unwrap() && *state.tray_available.lock().unwrap() {
                     api.prevent_close();
                     let _ = window.hide();
                 }
@@ -1116,23 +4449,53 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             send_query,
             cancel_query,
+            preview_spawn_plan,
+            estimate_query_tokens,
+            diagnose_failure,
+            query_timings,
             check_claude,
+            get_claude_version,
+            diagnose_path,
             save_mcp_config,
             load_mcp_config,
             get_mcp_config_path,
+            list_mcp_servers,
+            add_mcp_server,
+            remove_mcp_server,
+            set_mcp_server_enabled,
+            check_mcp_servers,
+            validate_mcp_config,
+            repair_mcp_config,
             get_settings,
             save_settings,
+            set_close_to_tray,
+            export_settings_redacted,
+            relocate_data_dir,
             load_vault_context,
             load_memory_context,
             read_memory_file,
             write_memory_file,
             delete_memory_file,
+            rename_memory_file,
             append_memory,
             list_memory_dir,
+            list_memory_subdirs,
+            search_memory,
             list_sessions,
             save_session_file,
             load_session_file,
+            get_session_messages_page,
+            diff_sessions,
+            session_stats,
+            search_sessions,
+            compact_session,
+            restore_compacted_session,
+            export_session_readonly,
+            import_readonly,
             delete_session_file,
+            prune_sessions,
+            reconcile_sessions,
+            duplicate_session,
             update_session_title,
             toggle_session_pin,
             migrate_sessions_from_localstorage,
@@ -1141,20 +4504,169 @@ pub fn run() {
             save_projects,
             validate_directory,
             list_directory,
+            list_directory_tree,
+            directory_size,
             search_files,
+            search_file_contents,
             read_file_content,
+            read_file_range,
             create_file,
             create_directory,
+            rename_path,
+            delete_path,
+            trash_path,
+            preview_edit,
+            apply_or_reject_edit,
+            list_commands,
+            save_command,
+            delete_command,
+            run_command,
             append_analytics,
             load_analytics,
+            tail_analytics,
+            get_analytics_summary,
             save_temp_image,
+            clear_temp_images,
+            read_image_as_base64,
             scan_vault,
+            is_obsidian_vault,
+            scan_vault_streaming,
             read_vault_files,
+            read_chunk_region,
+            rename_vault_note,
             search::init_embedding_model,
+            search::cancel_embedding_init,
             search::embed_chunks,
+            search::embed_text,
+            search::chunk_markdown,
             search::search_vectors,
+            search::search_hybrid,
+            search::clear_search_cache,
+            search::check_search_ready,
+            search::prune_vectors,
+            search::compact_vectors,
+            search::chunks_needing_reindex,
+            search::content_hash_algorithm,
+            search::index_stats,
+            search::clear_index,
+            search::embedding_model_info,
+            start_auto_reindex,
+            stop_auto_reindex,
+            search::search_notes,
+            search::find_duplicates,
             search::get_embedding_status
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                kill_all_queries(&app.state::<AppState>());
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch file under the system temp dir, removed on drop.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(content: Option<&str>) -> Self {
+            let path = std::env::temp_dir().join(format!("thunderclaude-test-{}.txt", uuid::Uuid::new_v4()));
+            if let Some(content) = content {
+                std::fs::write(&path, content).unwrap();
+            }
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    // ── strip_sensitive_keys ──
+
+    #[test]
+    fn sensitive_keys_match_whole_words() {
+        let mut value = serde_json::json!({
+            "apiKey": "a",
+            "api_key": "b",
+            "authToken": "c",
+            "GITHUB_TOKEN": "d",
+            "env": { "HOME": "/root" },
+            "mcpServers": { "x": { "command": "npx", "environment": {} } },
+            "embedding_tokenizer_threads": 4,
+            "author": "me",
+            "max_concurrent_queries": 2,
+        });
+        strip_sensitive_keys(&mut value);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "mcpServers": { "x": { "command": "npx" } },
+                "embedding_tokenizer_threads": 4,
+                "author": "me",
+                "max_concurrent_queries": 2,
+            })
+        );
+    }
+
+    // ── compute_edit_preview ──
+
+    #[test]
+    fn edit_preview_identical_content_is_empty() {
+        let file = TempFile::new(Some("one\ntwo\n"));
+        let preview = compute_edit_preview(file.path(), "one\ntwo\n").unwrap();
+        assert!(preview.diff.is_empty());
+        assert!(!preview.is_new_file);
+        assert_eq!((preview.lines_added, preview.lines_removed), (0, 0));
+    }
+
+    #[test]
+    fn edit_preview_counts_inserted_lines() {
+        let file = TempFile::new(Some("one\nthree\n"));
+        let preview = compute_edit_preview(file.path(), "one\ntwo\nthree\n").unwrap();
+        assert_eq!((preview.lines_added, preview.lines_removed), (1, 0));
+        assert!(preview.diff.contains("@@ -1,2 +1,3 @@"));
+        assert!(preview.diff.contains("\n+two\n"));
+    }
+
+    #[test]
+    fn edit_preview_counts_deleted_lines() {
+        let file = TempFile::new(Some("one\ntwo\nthree\n"));
+        let preview = compute_edit_preview(file.path(), "one\nthree\n").unwrap();
+        assert_eq!((preview.lines_added, preview.lines_removed), (0, 1));
+        assert!(preview.diff.contains("\n-two\n"));
+    }
+
+    #[test]
+    fn edit_preview_replace_is_a_delete_and_insert() {
+        let file = TempFile::new(Some("one\ntwo\nthree\n"));
+        let preview = compute_edit_preview(file.path(), "one\n2\nthree\n").unwrap();
+        assert_eq!((preview.lines_added, preview.lines_removed), (1, 1));
+        assert!(preview.diff.contains("\n-two\n+2\n"));
+        assert!(preview.diff.starts_with(&format!("--- a/{}\n+++ b/{}\n", file.path(), file.path())));
+    }
+
+    #[test]
+    fn edit_preview_missing_file_is_new() {
+        let file = TempFile::new(None);
+        let preview = compute_edit_preview(file.path(), "one\ntwo\n").unwrap();
+        assert!(preview.is_new_file);
+        assert_eq!((preview.lines_added, preview.lines_removed), (2, 0));
+    }
+
+    #[test]
+    fn edit_preview_unreadable_path_errors() {
+        let dir = std::env::temp_dir();
+        assert!(compute_edit_preview(dir.to_str().unwrap(), "x").is_err());
+    }
 }
@@ -1,4 +1,6 @@
 use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use rayon::prelude::*;
+use tauri::{Emitter, Manager};
 use serde::{Deserialize, Serialize};
 use std::io::{BufRead, Write as IoWrite};
 use std::path::PathBuf;
@@ -9,29 +11,96 @@ use std::path::PathBuf;
 pub struct EmbeddingStatus {
     pub initialized: bool,
     pub model_name: String,
+    /// Embedding length reported by the loaded model (0 until initialized)
     pub dimension: usize,
     pub chunks_indexed: usize,
     pub last_indexed: Option<u64>,
     pub indexing_in_progress: bool,
+    /// Init stage: "idle", "downloading", "loading-model", "loading-index" or "ready"
+    pub phase: &'static str,
 }
 
 impl Default for EmbeddingStatus {
     fn default() -> Self {
         Self {
             initialized: false,
-            model_name: "all-MiniLM-L6-v2".to_string(),
-            dimension: 384,
+            model_name: DEFAULT_EMBEDDING_MODEL.to_string(),
+            dimension: 0,
             chunks_indexed: 0,
             last_indexed: None,
             indexing_in_progress: false,
+            phase: "idle",
         }
     }
 }
 
+/// Where the embedding model weights live and how long the last load took.
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbeddingModelInfo {
+    pub model_name: String,
+    /// fastembed / HuggingFace cache root (HF_HOME, FASTEMBED_CACHE_DIR or ./.fastembed_cache)
+    pub cache_dir: String,
+    /// Repo folder for this model inside the cache
+    pub model_dir: String,
+    /// Weights already downloaded (false = first init will download them)
+    pub present: bool,
+    pub size_bytes: u64,
+    /// Wall time of the last successful model load, in milliseconds
+    pub last_load_ms: Option<u64>,
+}
+
+/// Outcome of one scan→diff→embed pass (see `reindex_vault`).
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReindexSummary {
+    pub files_scanned: usize,
+    pub files_reindexed: usize,
+    pub chunks_removed: usize,
+    pub chunks_embedded: usize,
+}
+
+/// Whether vector search can work: the model's output length must match the
+/// dimension of both the in-memory and the persisted index.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchReadiness {
+    pub model_initialized: bool,
+    /// 0 until the model is initialized
+    pub model_dimension: usize,
+    /// Persisted index dimension, or the in-memory one when nothing is on disk
+    pub index_dimension: usize,
+    pub compatible: bool,
+    pub message: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
 pub struct VectorMatch {
     pub id: String,
     pub score: f32,
+    /// Note the chunk came from (vault-relative path)
+    pub source: String,
+    pub heading: Option<String>,
+    pub modified_at: u64,
+}
+
+/// An embed-ready piece of a markdown file (see `chunk_markdown`).
+#[derive(Serialize)]
+pub struct Chunk {
+    pub id: String,
+    pub text: String,
+    /// Nearest heading above the chunk (None before the first heading)
+    pub heading: Option<String>,
+    /// Byte offset of `text` within the content
+    pub offset: usize,
+}
+
+/// A group of notes whose averaged embeddings are all linked above the threshold.
+#[derive(Serialize)]
+pub struct DuplicateCluster {
+    pub sources: Vec<String>,
+    /// Highest pairwise similarity inside the cluster
+    pub max_score: f32,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -41,23 +110,182 @@ struct ChunkMeta {
     heading: Option<String>,
     content_hash: String,
     modified_at: u64,
+    /// Byte offset/length of the chunk within its source file (for region reads)
+    #[serde(default)]
+    offset: Option<u64>,
+    #[serde(default)]
+    length: Option<u64>,
 }
 
 // ── State ────────────────────────────────────────────────────────────────────
 
+/// Everything that shapes a `search_vectors` result besides the index itself.
+#[derive(PartialEq, Eq, Hash)]
+struct SearchCacheKey {
+    query: String,
+    top_k: usize,
+    /// MMR lambda as raw bits (f32 isn't Hash)
+    diversity: Option<u32>,
+    source_prefix: Option<String>,
+}
+
+/// `search_vectors` results, tagged with the index version they were computed at.
+type SearchCache = std::collections::HashMap<SearchCacheKey, (u64, Vec<VectorMatch>)>;
+
 pub struct SearchState {
-    embedder: tokio::sync::Mutex<Option<TextEmbedding>>,
+    embedder: tokio::sync::Mutex<Option<std::sync::Arc<TextEmbedding>>>,
     status: std::sync::Mutex<EmbeddingStatus>,
     index: tokio::sync::Mutex<VectorIndex>,
+    /// Optional note-level index: one averaged vector per source (id = source path)
+    notes: tokio::sync::Mutex<VectorIndex>,
+    /// Max texts per `embed` call (None = `DEFAULT_EMBED_BATCH_SIZE`)
+    batch_size: std::sync::Mutex<Option<usize>>,
+    /// Dedicated pool when a tokenizer thread count is configured (None = rayon
+    /// global pool). fastembed tokenizes on the current rayon pool; ONNX Runtime
+    /// inference keeps its own per-session threads.
+    pool: std::sync::Mutex<Option<std::sync::Arc<rayon::ThreadPool>>>,
+    last_load_ms: std::sync::Mutex<Option<u64>>,
+    search_cache: std::sync::Mutex<SearchCache>,
+    /// Keyword index over the same chunks as `index` (for `search_hybrid`)
+    bm25: tokio::sync::Mutex<Bm25Index>,
+    /// Fired by `cancel_embedding_init` while a model load is in flight
+    init_cancel: std::sync::Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    /// Serializes `init_model` calls; `embedder` is only locked to swap the model
+    init_lock: tokio::sync::Mutex<()>,
+    /// Notified whenever an `IndexingGuard` is released
+    indexing_done: tokio::sync::Notify,
+    /// Modified time of vault files that produced no chunks, so reindex
+    /// doesn't re-read them every pass
+    empty_sources: std::sync::Mutex<std::collections::HashMap<String, u64>>,
 }
 
 impl SearchState {
     pub fn new() -> Self {
+        // Show the last run's index figures before the model loads. Without a
+        // saved status, the index marker still names the selected model — falling
+        // back to the default there would wipe the index on the next init.
+        let mut status = EmbeddingStatus::default();
+        if let Some(saved) = load_status() {
+            if let Ok((model_name, _)) = resolve_embedding_model(&saved.model_name) {
+                status.model_name = model_name.to_string();
+                status.chunks_indexed = saved.chunks_indexed;
+                status.last_indexed = saved.last_indexed;
+            }
+        } else if let Ok(marker) = std::fs::read_to_string(index_model_marker()) {
+            if let Ok((model_name, _)) = resolve_embedding_model(&marker) {
+                status.model_name = model_name.to_string();
+            }
+        }
         Self {
             embedder: tokio::sync::Mutex::new(None),
-            status: std::sync::Mutex::new(EmbeddingStatus::default()),
-            index: tokio::sync::Mutex::new(VectorIndex::new()),
+            status: std::sync::Mutex::new(status),
+            index: tokio::sync::Mutex::new(VectorIndex::new(0)),
+            notes: tokio::sync::Mutex::new(VectorIndex::new(0)),
+            batch_size: std::sync::Mutex::new(None),
+            pool: std::sync::Mutex::new(None),
+            last_load_ms: std::sync::Mutex::new(None),
+            search_cache: std::sync::Mutex::new(std::collections::HashMap::new()),
+            bm25: tokio::sync::Mutex::new(Bm25Index::default()),
+            init_cancel: std::sync::Mutex::new(None),
+            init_lock: tokio::sync::Mutex::new(()),
+            indexing_done: tokio::sync::Notify::new(),
+            empty_sources: std::sync::Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    /// Apply the embedding batch size / tokenizer thread count from Settings.
+    pub fn set_embedding_options(&self, batch_size: Option<usize>, tokenizer_threads: Option<usize>) {
+        *self.batch_size.lock().unwrap() = batch_size.filter(|&b| b > 0);
+        *self.pool.lock().unwrap() = tokenizer_threads.filter(|&t| t > 0).and_then(|t| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(t)
+                .build()
+                .map_err(|e| eprintln!("Warning: Failed to build tokenizer thread pool: {}", e))
+                .ok()
+                .map(std::sync::Arc::new)
+        });
+    }
+
+    /// Claim `indexing_in_progress`; None if another index run already holds it.
+    /// The flag is released when the guard drops (including on task abort).
+    pub fn try_begin_indexing(&self) -> Option<IndexingGuard<'_>> {
+        let mut status = self.status.lock().unwrap();
+        if status.indexing_in_progress {
+            return None;
         }
+        status.indexing_in_progress = true;
+        Some(IndexingGuard(self))
+    }
+
+    /// Claim `indexing_in_progress`, waiting for any running index pass to finish.
+    pub async fn begin_indexing(&self) -> IndexingGuard<'_> {
+        loop {
+            let released = self.indexing_done.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            if let Some(guard) = self.try_begin_indexing() {
+                return guard;
+            }
+            released.await;
+        }
+    }
+
+    /// Embed `texts`, sequentially in `batch_size` groups (default 256, capping
+    /// peak memory) with tokenization on the configured pool, off the async runtime.
+    /// `on_progress(done, total)` runs after each group.
+    async fn embed_batched(
+        &self,
+        embedder: std::sync::Arc<TextEmbedding>,
+        texts: Vec<String>,
+        on_progress: impl Fn(usize, usize) + Send + Sync + 'static,
+    ) -> Result<Vec<Vec<f32>>, String> {
+        let size = self.batch_size.lock().unwrap().unwrap_or(DEFAULT_EMBED_BATCH_SIZE);
+        let pool = self.pool.lock().unwrap().clone();
+        let run = move || -> Result<Vec<Vec<f32>>, String> {
+            let total = texts.len();
+            let mut out = Vec::with_capacity(total);
+            for group in texts.chunks(size) {
+                out.extend(
+                    embedder
+                        .embed(group.to_vec(), Some(size))
+                        .map_err(|e| format!("Embedding failed: {}", e))?,
+                );
+                on_progress(out.len(), total);
+            }
+            Ok(out)
+        };
+        tokio::task::spawn_blocking(move || match pool {
+            Some(pool) => pool.install(run),
+            None => run(),
+        })
+        .await
+        .map_err(|e| format!("Embedding task failed: {}", e))?
+    }
+}
+
+/// Embed a single search query on a blocking thread.
+async fn embed_query(embedder: std::sync::Arc<TextEmbedding>, query: String) -> Result<Vec<f32>, String> {
+    tokio::task::spawn_blocking(move || {
+        embedder
+            .embed(vec![query], None)
+            .map_err(|e| format!("Query embedding failed: {}", e))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Failed to generate query embedding".to_string())
+    })
+    .await
+    .map_err(|e| format!("Query embedding task failed: {}", e))?
+}
+
+/// Texts per embedding call when no batch size is configured.
+const DEFAULT_EMBED_BATCH_SIZE: usize = 256;
+
+pub struct IndexingGuard<'a>(&'a SearchState);
+
+impl Drop for IndexingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.status.lock().unwrap().indexing_in_progress = false;
+        self.0.indexing_done.notify_waiters();
     }
 }
 
@@ -68,38 +296,64 @@ struct VectorIndex {
     ids: Vec<String>,
     /// Flat vector storage: ids.len() × dimension
     vectors: Vec<f32>,
+    /// L2 norm of each stored vector (aligned with ids), computed at insert/load
+    norms: Vec<f32>,
     /// Metadata per chunk
     meta: Vec<ChunkMeta>,
     dimension: usize,
+    /// Slot of the live entry for each chunk ID
+    positions: std::collections::HashMap<String, usize>,
+    /// Bumped whenever the set of live vectors changes (cache invalidation)
+    version: u64,
+    /// Tombstone flags (aligned with ids): superseded slots awaiting compaction
+    dead: Vec<bool>,
+    tombstones: usize,
 }
 
 impl VectorIndex {
-    fn new() -> Self {
+    fn new(dimension: usize) -> Self {
         Self {
             ids: Vec::new(),
             vectors: Vec::new(),
+            norms: Vec::new(),
             meta: Vec::new(),
-            dimension: 384,
+            dimension,
+            positions: std::collections::HashMap::new(),
+            version: 0,
+            dead: Vec::new(),
+            tombstones: 0,
         }
     }
 
+    /// Number of live (non-tombstoned) chunks.
     fn len(&self) -> usize {
-        self.ids.len()
+        self.ids.len() - self.tombstones
     }
 
-    /// Add a batch of vectors with their IDs and metadata.
-    fn add_batch(&mut self, ids: &[String], vectors: &[Vec<f32>], meta: Vec<ChunkMeta>) {
+    /// Add a batch of vectors with their IDs and metadata. Rejects the whole
+    /// batch if any vector's length differs from the index dimension.
+    fn add_batch(&mut self, ids: &[String], vectors: &[Vec<f32>], meta: Vec<ChunkMeta>) -> Result<(), String> {
+        if let Some(bad) = vectors.iter().find(|v| v.len() != self.dimension) {
+            return Err(format!(
+                "Embedding dimension {} doesn't match index dimension {}",
+                bad.len(),
+                self.dimension
+            ));
+        }
+        self.version += 1;
+
         for (i, id) in ids.iter().enumerate() {
-            // Remove old version if exists
-            if let Some(pos) = self.ids.iter().position(|x| x == id) {
-                self.ids.remove(pos);
-                let start = pos * self.dimension;
-                self.vectors.drain(start..start + self.dimension);
-                self.meta.remove(pos);
+            // Tombstone the old version instead of draining it out of the flat buffer
+            if let Some(&pos) = self.positions.get(id) {
+                self.dead[pos] = true;
+                self.tombstones += 1;
             }
 
+            self.positions.insert(id.clone(), self.ids.len());
             self.ids.push(id.clone());
+            self.dead.push(false);
             self.vectors.extend_from_slice(&vectors[i]);
+            self.norms.push(l2_norm(&vectors[i]));
             if i < meta.len() {
                 self.meta.push(ChunkMeta {
                     id: id.clone(),
@@ -107,53 +361,233 @@ impl VectorIndex {
                 });
             }
         }
+
+        // Keep dead space bounded so a long reindex doesn't double memory
+        if self.tombstones > self.ids.len() / 2 {
+            self.compact();
+        }
+        Ok(())
+    }
+
+    /// Rebuild the flat arrays without tombstoned entries.
+    /// Returns the number of tombstones dropped.
+    fn compact(&mut self) -> usize {
+        let dropped = self.tombstones;
+        if dropped > 0 {
+            self.retain(|_| true);
+        }
+        dropped
     }
 
     /// Cosine similarity search. Returns top-K results sorted by score.
-    fn search(&self, query_vector: &[f32], top_k: usize) -> Vec<VectorMatch> {
-        if self.ids.is_empty() || query_vector.len() != self.dimension {
+    /// With `source_prefix`, only chunks whose source starts with it are scored.
+    fn search(&self, query_vector: &[f32], top_k: usize, source_prefix: Option<&str>) -> Vec<VectorMatch> {
+        self.top_scores(query_vector, top_k, source_prefix)
+            .into_iter()
+            .map(|(i, s)| self.to_match(i, s))
+            .collect()
+    }
+
+    /// Maximal Marginal Relevance: from the best `top_k × MMR_CANDIDATE_FACTOR`
+    /// candidates, repeatedly pick the one maximizing
+    /// `lambda · sim(query) − (1 − lambda) · max sim(already selected)`.
+    /// Scores in the result are still query similarities.
+    fn search_mmr(
+        &self,
+        query_vector: &[f32],
+        top_k: usize,
+        lambda: f32,
+        source_prefix: Option<&str>,
+    ) -> Vec<VectorMatch> {
+        let lambda = lambda.clamp(0.0, 1.0);
+        let mut remaining =
+            self.top_scores(query_vector, top_k.saturating_mul(MMR_CANDIDATE_FACTOR), source_prefix);
+        let mut selected: Vec<(usize, f32)> = Vec::with_capacity(top_k);
+
+        while selected.len() < top_k && !remaining.is_empty() {
+            let best = remaining
+                .iter()
+                .enumerate()
+                .map(|(pos, &(i, sim))| {
+                    let redundancy = selected
+                        .iter()
+                        .map(|&(j, _)| self.cosine(i, j))
+                        .reduce(f32::max)
+                        .unwrap_or(0.0);
+                    (pos, lambda * sim - (1.0 - lambda) * redundancy)
+                })
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(pos, _)| pos)
+                .unwrap_or(0);
+            selected.push(remaining.remove(best));
+        }
+
+        selected.into_iter().map(|(i, s)| self.to_match(i, s)).collect()
+    }
+
+    /// Cosine similarity between two stored vectors.
+    fn cosine(&self, a: usize, b: usize) -> f32 {
+        let (na, nb) = (self.norms[a], self.norms[b]);
+        if na == 0.0 || nb == 0.0 {
+            return 0.0;
+        }
+        let va = &self.vectors[a * self.dimension..(a + 1) * self.dimension];
+        let vb = &self.vectors[b * self.dimension..(b + 1) * self.dimension];
+        va.iter().zip(vb).map(|(x, y)| x * y).sum::<f32>() / (na * nb)
+    }
+
+    /// Cosine similarity between a query vector and one stored vector.
+    fn query_cosine(&self, query_vector: &[f32], i: usize) -> f32 {
+        let (qn, dn) = (l2_norm(query_vector), self.norms[i]);
+        if qn == 0.0 || dn == 0.0 || query_vector.len() != self.dimension {
+            return 0.0;
+        }
+        let doc = &self.vectors[i * self.dimension..(i + 1) * self.dimension];
+        query_vector.iter().zip(doc).map(|(q, d)| q * d).sum::<f32>() / (qn * dn)
+    }
+
+    fn to_match(&self, i: usize, score: f32) -> VectorMatch {
+        let meta = self.meta.get(i);
+        VectorMatch {
+            id: self.ids[i].clone(),
+            score,
+            source: meta.map(|m| m.source.clone()).unwrap_or_default(),
+            heading: meta.and_then(|m| m.heading.clone()),
+            modified_at: meta.map(|m| m.modified_at).unwrap_or(0),
+        }
+    }
+
+    /// Positive cosine scores of the `top_k` most similar live vectors, best first.
+    fn top_scores(&self, query_vector: &[f32], top_k: usize, source_prefix: Option<&str>) -> Vec<(usize, f32)> {
+        if top_k == 0 || self.ids.is_empty() || query_vector.len() != self.dimension {
             return Vec::new();
         }
 
         // Precompute query norm
-        let q_norm: f32 = query_vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let q_norm = l2_norm(query_vector);
         if q_norm == 0.0 {
             return Vec::new();
         }
 
-        let mut scores: Vec<(usize, f32)> = Vec::with_capacity(self.ids.len());
+        // Score every live vector in parallel over the flat buffer
+        let mut scores: Vec<(usize, f32)> = self
+            .vectors
+            .par_chunks(self.dimension)
+            .enumerate()
+            .filter(|(i, _)| !self.dead[*i])
+            .filter(|(i, _)| match (source_prefix, self.meta.get(*i)) {
+                (Some(prefix), Some(m)) => m.source.starts_with(prefix),
+                (Some(_), None) => false,
+                (None, _) => true,
+            })
+            .map(|(i, doc_vec)| {
+                let dot: f32 = query_vector.iter().zip(doc_vec).map(|(q, d)| q * d).sum();
+                let d_norm = self.norms[i];
+                let score = if d_norm > 0.0 {
+                    dot / (q_norm * d_norm)
+                } else {
+                    0.0
+                };
+                (i, score)
+            })
+            .collect();
+
+        // Partial select for top-K, then sort just those
+        let by_score = |a: &(usize, f32), b: &(usize, f32)| {
+            b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal)
+        };
+        if scores.len() > top_k {
+            scores.select_nth_unstable_by(top_k - 1, by_score);
+            scores.truncate(top_k);
+        }
+        scores.sort_by(by_score);
+        scores.retain(|(_, s)| *s > 0.0);
+        scores
+    }
 
-        for i in 0..self.ids.len() {
+    /// Keep only live chunks whose metadata passes `keep`, compacting the flat
+    /// vector buffer (and dropping tombstones) in one pass. Returns the number
+    /// of live chunks removed.
+    fn retain(&mut self, keep: impl Fn(&ChunkMeta) -> bool) -> usize {
+        let before = self.len();
+        let mut ids = Vec::with_capacity(before);
+        let mut vectors = Vec::with_capacity(before * self.dimension);
+        let mut norms = Vec::with_capacity(before);
+        let mut meta = Vec::with_capacity(before);
+        for (i, m) in self.meta.iter().enumerate() {
+            if self.dead[i] || !keep(m) {
+                continue;
+            }
             let offset = i * self.dimension;
-            let doc_vec = &self.vectors[offset..offset + self.dimension];
+            ids.push(self.ids[i].clone());
+            vectors.extend_from_slice(&self.vectors[offset..offset + self.dimension]);
+            norms.push(self.norms[i]);
+            meta.push(m.clone());
+        }
+        self.ids = ids;
+        self.vectors = vectors;
+        self.norms = norms;
+        self.meta = meta;
+        self.rebuild_positions();
+        let removed = before - self.ids.len();
+        if removed > 0 {
+            self.version += 1;
+        }
+        removed
+    }
 
-            let mut dot = 0.0f32;
-            let mut d_norm = 0.0f32;
-            for j in 0..self.dimension {
-                dot += query_vector[j] * doc_vec[j];
-                d_norm += doc_vec[j] * doc_vec[j];
+    /// Reset tombstones and re-derive the ID → slot map (all entries live).
+    fn rebuild_positions(&mut self) {
+        self.positions = self.ids.iter().enumerate().map(|(i, id)| (id.clone(), i)).collect();
+        self.dead = vec![false; self.ids.len()];
+        self.tombstones = 0;
+    }
+
+    /// Latest stored `modified_at` per source (live chunks only).
+    fn source_modified_times(&self) -> std::collections::HashMap<String, u64> {
+        let mut times = std::collections::HashMap::new();
+        for (i, m) in self.meta.iter().enumerate() {
+            if self.dead[i] {
+                continue;
             }
-            d_norm = d_norm.sqrt();
+            let t = times.entry(m.source.clone()).or_insert(m.modified_at);
+            *t = (*t).max(m.modified_at);
+        }
+        times
+    }
 
-            let score = if d_norm > 0.0 {
-                dot / (q_norm * d_norm)
-            } else {
-                0.0
+    /// Average the chunk vectors of each source into one unit-length note vector.
+    fn source_centroids(&self) -> Vec<(String, Vec<f32>)> {
+        let mut order: Vec<String> = Vec::new();
+        let mut sums: std::collections::HashMap<&str, Vec<f32>> = std::collections::HashMap::new();
+        for (i, m) in self.meta.iter().enumerate() {
+            if self.dead[i] {
+                continue;
+            }
+            let offset = i * self.dimension;
+            let vec = match self.vectors.get(offset..offset + self.dimension) {
+                Some(v) => v,
+                None => continue,
             };
-
-            scores.push((i, score));
+            let sum = sums.entry(m.source.as_str()).or_insert_with(|| {
+                order.push(m.source.clone());
+                vec![0.0; self.dimension]
+            });
+            for (s, v) in sum.iter_mut().zip(vec) {
+                *s += v;
+            }
         }
 
-        // Partial sort for top-K
-        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        scores.truncate(top_k);
-
-        scores
+        order
             .into_iter()
-            .filter(|(_, s)| *s > 0.0)
-            .map(|(i, s)| VectorMatch {
-                id: self.ids[i].clone(),
-                score: s,
+            .filter_map(|source| {
+                let mut v = sums.remove(source.as_str())?;
+                let norm = l2_norm(&v);
+                if norm == 0.0 {
+                    return None;
+                }
+                v.iter_mut().for_each(|x| *x /= norm);
+                Some((source, v))
             })
             .collect()
     }
@@ -174,13 +608,18 @@ impl VectorIndex {
         file.write_u32::<LittleEndian>(1).map_err(|e| e.to_string())?; // version
         file.write_u32::<LittleEndian>(self.dimension as u32)
             .map_err(|e| e.to_string())?;
-        file.write_u32::<LittleEndian>(self.ids.len() as u32)
+        file.write_u32::<LittleEndian>(self.len() as u32)
             .map_err(|e| e.to_string())?;
 
-        // Write packed f32 vectors
-        for v in &self.vectors {
-            file.write_f32::<LittleEndian>(*v)
-                .map_err(|e| e.to_string())?;
+        // Write packed f32 vectors (live entries only)
+        for (i, chunk) in self.vectors.chunks(self.dimension.max(1)).enumerate() {
+            if self.dead.get(i).copied().unwrap_or(false) {
+                continue;
+            }
+            for v in chunk {
+                file.write_f32::<LittleEndian>(*v)
+                    .map_err(|e| e.to_string())?;
+            }
         }
 
         // Write metadata as JSONL
@@ -188,7 +627,10 @@ impl VectorIndex {
         let mut meta_file = std::fs::File::create(&meta_path)
             .map_err(|e| format!("Failed to create meta file: {}", e))?;
 
-        for m in &self.meta {
+        for (i, m) in self.meta.iter().enumerate() {
+            if self.dead[i] {
+                continue;
+            }
             let json = serde_json::to_string(m).map_err(|e| e.to_string())?;
             writeln!(meta_file, "{}", json).map_err(|e| e.to_string())?;
         }
@@ -196,13 +638,28 @@ impl VectorIndex {
         Ok(())
     }
 
-    /// Load from disk.
-    fn load(dir: &std::path::Path) -> Result<Self, String> {
+    /// Dimension and vector count from a persisted index header, if one exists.
+    fn stored_header(dir: &std::path::Path) -> Option<(usize, usize)> {
+        use byteorder::{LittleEndian, ReadBytesExt};
+        let mut file = std::fs::File::open(dir.join("vault-vectors.bin")).ok()?;
+        let mut magic = [0u8; 4];
+        std::io::Read::read_exact(&mut file, &mut magic).ok()?;
+        if &magic != b"TCVX" {
+            return None;
+        }
+        let _version = file.read_u32::<LittleEndian>().ok()?;
+        let dimension = file.read_u32::<LittleEndian>().ok()? as usize;
+        let count = file.read_u32::<LittleEndian>().ok()? as usize;
+        Some((dimension, count))
+    }
+
+    /// Load from disk, checking the stored dimension against the model's.
+    fn load(dir: &std::path::Path, expected_dimension: usize) -> Result<Self, String> {
         let vec_path = dir.join("vault-vectors.bin");
         let meta_path = dir.join("vault-meta.jsonl");
 
         if !vec_path.exists() || !meta_path.exists() {
-            return Ok(Self::new());
+            return Ok(Self::new(expected_dimension));
         }
 
         // Read binary vectors
@@ -219,6 +676,15 @@ impl VectorIndex {
         let _version = file.read_u32::<LittleEndian>().map_err(|e| e.to_string())?;
         let dimension = file.read_u32::<LittleEndian>().map_err(|e| e.to_string())? as usize;
         let count = file.read_u32::<LittleEndian>().map_err(|e| e.to_string())? as usize;
+        if count == 0 {
+            return Ok(Self::new(expected_dimension));
+        }
+        if dimension != expected_dimension {
+            return Err(format!(
+                "Vector file dimension {} doesn't match model dimension {} (reindex required)",
+                dimension, expected_dimension
+            ));
+        }
 
         let mut vectors = vec![0.0f32; count * dimension];
         for v in vectors.iter_mut() {
@@ -242,23 +708,44 @@ impl VectorIndex {
             ids.push(m.id.clone());
             meta.push(m);
         }
+        if meta.len() != count {
+            return Err(format!(
+                "Vector file holds {} vectors but metadata has {} entries",
+                count,
+                meta.len()
+            ));
+        }
 
-        Ok(Self {
+        let norms = vectors.chunks(dimension).map(l2_norm).collect();
+        let mut index = Self {
             ids,
             vectors,
+            norms,
             meta,
             dimension,
-        })
+            positions: std::collections::HashMap::new(),
+            version: 0,
+            dead: Vec::new(),
+            tombstones: 0,
+        };
+        index.rebuild_positions();
+        Ok(index)
     }
 
-    #[allow(dead_code)]
     fn clear(&mut self) {
         self.ids.clear();
         self.vectors.clear();
+        self.norms.clear();
         self.meta.clear();
+        self.rebuild_positions();
+        self.version += 1;
     }
 }
 
+fn l2_norm(v: &[f32]) -> f32 {
+    v.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
+
 // Implement Clone for ChunkMeta manually since Deserialize is derived
 impl Clone for ChunkMeta {
     fn clone(&self) -> Self {
@@ -268,105 +755,704 @@ impl Clone for ChunkMeta {
             heading: self.heading.clone(),
             content_hash: self.content_hash.clone(),
             modified_at: self.modified_at,
+            offset: self.offset,
+            length: self.length,
         }
     }
 }
 
+// ── BM25 term index ──────────────────────────────────────────────────────────
+
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+#[derive(Serialize, Deserialize)]
+struct Bm25Doc {
+    /// Occurrences of each term in the chunk
+    tf: std::collections::HashMap<String, u32>,
+    /// Total token count of the chunk
+    len: u32,
+}
+
+/// Keyword index over chunk texts, keyed by chunk ID. Only the per-chunk term
+/// frequencies are persisted; postings and lengths are rebuilt on load.
+#[derive(Default, Serialize, Deserialize)]
+struct Bm25Index {
+    docs: std::collections::HashMap<String, Bm25Doc>,
+    /// Chunk IDs containing each term (document frequency = set size)
+    #[serde(skip)]
+    postings: std::collections::HashMap<String, std::collections::HashSet<String>>,
+    #[serde(skip)]
+    total_len: u64,
+    /// Changed since the last load/save; `save` is a no-op otherwise
+    #[serde(skip)]
+    dirty: bool,
+}
+
+/// Lowercased alphanumeric runs.
+fn bm25_tokens(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+impl Bm25Index {
+    /// Index (or re-index) one chunk's text.
+    fn upsert(&mut self, id: &str, text: &str) {
+        self.remove(id);
+        let tokens = bm25_tokens(text);
+        let mut tf: std::collections::HashMap<String, u32> = std::collections::HashMap::new();
+        for token in &tokens {
+            *tf.entry(token.clone()).or_insert(0) += 1;
+        }
+        self.insert_doc(id.to_string(), Bm25Doc { tf, len: tokens.len() as u32 });
+    }
+
+    /// Move every chunk of `other` into this index, replacing same-ID entries.
+    fn merge(&mut self, other: Bm25Index) {
+        for (id, doc) in other.docs {
+            self.remove(&id);
+            self.insert_doc(id, doc);
+        }
+    }
+
+    fn insert_doc(&mut self, id: String, doc: Bm25Doc) {
+        for term in doc.tf.keys() {
+            self.postings.entry(term.clone()).or_default().insert(id.clone());
+        }
+        self.total_len += doc.len as u64;
+        self.docs.insert(id, doc);
+        self.dirty = true;
+    }
+
+    fn remove(&mut self, id: &str) {
+        let Some(doc) = self.docs.remove(id) else { return };
+        for term in doc.tf.keys() {
+            if let Some(ids) = self.postings.get_mut(term) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+        self.total_len -= doc.len as u64;
+        self.dirty = true;
+    }
+
+    /// Drop every chunk whose ID fails `keep`.
+    fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        let gone: Vec<String> = self.docs.keys().filter(|id| !keep(id)).cloned().collect();
+        for id in gone {
+            self.remove(&id);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.docs.clear();
+        self.postings.clear();
+        self.total_len = 0;
+        self.dirty = true;
+    }
+
+    /// Okapi BM25 scores of the `top_k` best-matching chunks, best first.
+    fn search(&self, query: &str, top_k: usize) -> Vec<(String, f32)> {
+        let n = self.docs.len();
+        if n == 0 || top_k == 0 {
+            return Vec::new();
+        }
+        let avg_len = self.total_len as f32 / n as f32;
+
+        let mut terms = bm25_tokens(query);
+        terms.sort();
+        terms.dedup();
+
+        let mut scores: std::collections::HashMap<&str, f32> = std::collections::HashMap::new();
+        for term in &terms {
+            let Some(ids) = self.postings.get(term) else { continue };
+            let df = ids.len() as f32;
+            let idf = (1.0 + (n as f32 - df + 0.5) / (df + 0.5)).ln();
+            for id in ids {
+                let doc = &self.docs[id];
+                let tf = doc.tf.get(term).copied().unwrap_or(0) as f32;
+                let norm = 1.0 - BM25_B + BM25_B * doc.len as f32 / avg_len.max(1.0);
+                *scores.entry(id.as_str()).or_insert(0.0) += idf * tf * (BM25_K1 + 1.0) / (tf + BM25_K1 * norm);
+            }
+        }
+
+        let mut ranked: Vec<(String, f32)> = scores.into_iter().map(|(id, s)| (id.to_string(), s)).collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        ranked.truncate(top_k);
+        ranked
+    }
+
+    /// Write to `dir` if anything changed since the last load or save.
+    fn save(&mut self, dir: &std::path::Path) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create dir: {}", e))?;
+        let json = serde_json::to_vec(self).map_err(|e| format!("Failed to serialize BM25 index: {}", e))?;
+        std::fs::write(dir.join(BM25_FILE), json).map_err(|e| format!("Failed to write BM25 index: {}", e))?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Load from `dir`; an empty index if nothing was saved yet.
+    fn load(dir: &std::path::Path) -> Result<Self, String> {
+        let bytes = match std::fs::read(dir.join(BM25_FILE)) {
+            Ok(b) => b,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("Failed to read BM25 index: {}", e)),
+        };
+        let stored: Self =
+            serde_json::from_slice(&bytes).map_err(|e| format!("Failed to parse BM25 index: {}", e))?;
+        let mut index = Self::default();
+        index.merge(stored);
+        index.dirty = false;
+        Ok(index)
+    }
+}
+
 // ── Storage paths ────────────────────────────────────────────────────────────
 
+/// Cache root fastembed downloads into: HF_HOME wins, then FASTEMBED_CACHE_DIR,
+/// then `.fastembed_cache` relative to the working directory.
+fn model_cache_dir() -> PathBuf {
+    let dir = std::env::var("HF_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(fastembed::get_cache_dir()));
+    if dir.is_relative() {
+        if let Ok(cwd) = std::env::current_dir() {
+            return cwd.join(dir);
+        }
+    }
+    dir
+}
+
+fn dir_size(path: &std::path::Path) -> u64 {
+    let entries = match std::fs::read_dir(path) {
+        Ok(e) => e,
+        Err(_) => return 0,
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(m) if m.is_dir() => dir_size(&entry.path()),
+            Ok(m) => m.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Repo folder for `model` inside the cache, and whether its weights are
+/// already downloaded (hf-hub layout: snapshots/<revision>/<model_file>).
+fn model_dir(model: &EmbeddingModel) -> Result<(PathBuf, bool), String> {
+    let info = TextEmbedding::get_model_info(model)
+        .map_err(|e| format!("Failed to get model info: {}", e))?;
+    let dir = model_cache_dir().join(format!("models--{}", info.model_code.replace('/', "--")));
+    let present = std::fs::read_dir(dir.join("snapshots"))
+        .map(|entries| entries.flatten().any(|e| e.path().join(&info.model_file).exists()))
+        .unwrap_or(false);
+    Ok((dir, present))
+}
+
 fn vectors_dir() -> PathBuf {
-    let home = std::env::var("USERPROFILE")
-        .or_else(|_| std::env::var("HOME"))
-        .unwrap_or_default();
-    PathBuf::from(home).join(".thunderclaude").join("vectors")
+    crate::thunderclaude_dir().join("vectors")
+}
+
+/// BM25 term frequencies, stored next to the vault vectors.
+const BM25_FILE: &str = "bm25.json";
+
+fn note_vectors_dir() -> PathBuf {
+    vectors_dir().join("notes")
+}
+
+/// Vault files that produced no chunks, with the modified time they were read at.
+const EMPTY_SOURCES_FILE: &str = "empty-sources.json";
+
+fn load_empty_sources(dir: &std::path::Path) -> std::collections::HashMap<String, u64> {
+    std::fs::read_to_string(dir.join(EMPTY_SOURCES_FILE))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_empty_sources(dir: &std::path::Path, sources: &std::collections::HashMap<String, u64>) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create dir: {}", e))?;
+    let json = serde_json::to_string(sources).map_err(|e| format!("Failed to serialize empty sources: {}", e))?;
+    std::fs::write(dir.join(EMPTY_SOURCES_FILE), json).map_err(|e| format!("Failed to write empty sources: {}", e))
+}
+
+/// Last known `EmbeddingStatus` figures, restored at startup.
+const STATUS_FILE: &str = "status.json";
+
+#[derive(Serialize, Deserialize)]
+struct PersistedStatus {
+    model_name: String,
+    chunks_indexed: usize,
+    last_indexed: Option<u64>,
+}
+
+/// Saved status, if it belongs to the model that built the persisted index.
+fn load_status() -> Option<PersistedStatus> {
+    let data = std::fs::read_to_string(vectors_dir().join(STATUS_FILE)).ok()?;
+    let saved: PersistedStatus = serde_json::from_str(&data).ok()?;
+    match std::fs::read_to_string(index_model_marker()) {
+        Ok(model) if model.trim() != saved.model_name => None,
+        _ => Some(saved),
+    }
+}
+
+fn save_status(status: &EmbeddingStatus) {
+    let saved = PersistedStatus {
+        model_name: status.model_name.clone(),
+        chunks_indexed: status.chunks_indexed,
+        last_indexed: status.last_indexed,
+    };
+    let result = std::fs::create_dir_all(vectors_dir()).and_then(|_| {
+        std::fs::write(
+            vectors_dir().join(STATUS_FILE),
+            serde_json::to_string_pretty(&saved).unwrap_or_default(),
+        )
+    });
+    if let Err(e) = result {
+        eprintln!("Warning: Failed to save embedding status: {}", e);
+    }
+}
+
+/// Records which model built the persisted index (vectors from different
+/// models aren't comparable, even at the same dimension).
+fn index_model_marker() -> PathBuf {
+    vectors_dir().join("vault-model.txt")
+}
+
+/// Delete the persisted index files in `dir` (missing files are fine).
+fn remove_index_files(dir: &std::path::Path) -> Result<(), String> {
+    for name in ["vault-vectors.bin", "vault-meta.jsonl", BM25_FILE, EMPTY_SOURCES_FILE] {
+        match std::fs::remove_file(dir.join(name)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to remove {}: {}", name, e)),
+        }
+    }
+    Ok(())
+}
+
+// ── Model selection ──────────────────────────────────────────────────────────
+
+const DEFAULT_EMBEDDING_MODEL: &str = "all-MiniLM-L6-v2";
+
+/// Selectable text embedding models, by upstream model name.
+fn embedding_models() -> Vec<(&'static str, EmbeddingModel)> {
+    vec![
+        ("all-MiniLM-L6-v2", EmbeddingModel::AllMiniLML6V2),
+        ("all-MiniLM-L12-v2", EmbeddingModel::AllMiniLML12V2),
+        ("bge-small-en-v1.5", EmbeddingModel::BGESmallENV15),
+        ("bge-base-en-v1.5", EmbeddingModel::BGEBaseENV15),
+        ("bge-large-en-v1.5", EmbeddingModel::BGELargeENV15),
+        ("bge-small-zh-v1.5", EmbeddingModel::BGESmallZHV15),
+        ("nomic-embed-text-v1.5", EmbeddingModel::NomicEmbedTextV15),
+        ("paraphrase-multilingual-MiniLM-L12-v2", EmbeddingModel::ParaphraseMLMiniLML12V2),
+        ("paraphrase-multilingual-mpnet-base-v2", EmbeddingModel::ParaphraseMLMpnetBaseV2),
+        ("multilingual-e5-small", EmbeddingModel::MultilingualE5Small),
+        ("multilingual-e5-base", EmbeddingModel::MultilingualE5Base),
+        ("multilingual-e5-large", EmbeddingModel::MultilingualE5Large),
+        ("mxbai-embed-large-v1", EmbeddingModel::MxbaiEmbedLargeV1),
+    ]
+}
+
+/// Map a model name (case-insensitive) to its canonical name and fastembed variant.
+fn resolve_embedding_model(name: &str) -> Result<(&'static str, EmbeddingModel), String> {
+    let models = embedding_models();
+    let known: Vec<&str> = models.iter().map(|(n, _)| *n).collect();
+    models
+        .into_iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| format!("Unknown embedding model '{}'. Known models: {}", name, known.join(", ")))
 }
 
 // ── Tauri commands ───────────────────────────────────────────────────────────
 
-/// Initialize the embedding model. Downloads on first use (~22MB), cached after.
+/// Initialize the embedding model (default all-MiniLM-L6-v2). Downloads on
+/// first use, cached after. Switching models discards the persisted index,
+/// which then needs a re-index.
 #[tauri::command]
 pub async fn init_embedding_model(
+    app: tauri::AppHandle,
     state: tauri::State<'_, SearchState>,
+    model: Option<String>,
 ) -> Result<EmbeddingStatus, String> {
-    let mut embedder_lock = state.embedder.lock().await;
+    init_model(&app, &state, model.as_deref()).await
+}
 
-    if embedder_lock.is_some() {
+/// Abort an in-flight `init_embedding_model`. Returns false when nothing was
+/// initializing. A download already underway finishes in the background and
+/// is cached, but the model is not loaded.
+#[tauri::command]
+pub async fn cancel_embedding_init(state: tauri::State<'_, SearchState>) -> Result<bool, String> {
+    Ok(match state.init_cancel.lock().unwrap().take() {
+        Some(tx) => tx.send(()).is_ok(),
+        None => false,
+    })
+}
+
+/// How often `embedding-download-progress` is emitted while weights download.
+const DOWNLOAD_PROGRESS_INTERVAL_MS: u64 = 500;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadProgress {
+    model: String,
+    phase: &'static str,
+    /// Bytes in the model's cache folder so far
+    downloaded_bytes: u64,
+}
+
+fn set_phase(app: &tauri::AppHandle, state: &SearchState, model: &str, phase: &'static str, downloaded_bytes: u64) {
+    state.status.lock().unwrap().phase = phase;
+    let _ = app.emit(
+        "embedding-download-progress",
+        DownloadProgress { model: model.to_string(), phase, downloaded_bytes },
+    );
+}
+
+/// Shared init path for the command and the startup warm-up task.
+/// `model: None` keeps the currently selected model. Loading runs on a
+/// blocking thread; progress is emitted as `embedding-download-progress`
+/// ({ model, phase, downloadedBytes }).
+pub async fn init_model(
+    app: &tauri::AppHandle,
+    state: &SearchState,
+    model: Option<&str>,
+) -> Result<EmbeddingStatus, String> {
+    let requested = model
+        .map(str::to_string)
+        .unwrap_or_else(|| state.status.lock().unwrap().model_name.clone());
+    let (model_name, model) = resolve_embedding_model(&requested)?;
+
+    // A caller queued behind another init finds the model it wants already loaded
+    let _init = state.init_lock.lock().await;
+    if state.embedder.lock().await.is_some() && state.status.lock().unwrap().model_name == model_name {
         let status = state.status.lock().unwrap().clone();
         return Ok(status);
     }
 
-    // Initialize fastembed with all-MiniLM-L6-v2
-    let mut opts = InitOptions::new(EmbeddingModel::AllMiniLML6V2);
+    // The current model keeps serving embeds and searches until the new one is
+    // loaded, and stays if the load is cancelled or fails
+    let (cache_dir, present) = model_dir(&model)?;
+    let downloading = !present;
+    set_phase(app, state, model_name, if downloading { "downloading" } else { "loading-model" }, dir_size(&cache_dir));
+
+    let (cancel_tx, mut cancel_rx) = tokio::sync::oneshot::channel();
+    *state.init_cancel.lock().unwrap() = Some(cancel_tx);
+
+    let mut opts = InitOptions::new(model);
     opts.show_download_progress = false;
-    let model = TextEmbedding::try_new(opts)
-        .map_err(|e| format!("Failed to init embedding model: {}", e))?;
+    let started = std::time::Instant::now();
+    let mut load = tokio::task::spawn_blocking(move || {
+        let model = TextEmbedding::try_new(opts)
+            .map_err(|e| format!("Failed to init embedding model: {}", e))?;
+        // Take the dimension from the model's actual output rather than assuming one
+        let dimension = model
+            .embed(vec!["dimension probe"], None)
+            .map_err(|e| format!("Failed to probe embedding dimension: {}", e))?
+            .first()
+            .map(|v| v.len())
+            .ok_or("Embedding model returned no output")?;
+        Ok::<_, String>((model, dimension))
+    });
+
+    let mut ticker = tokio::time::interval(std::time::Duration::from_millis(DOWNLOAD_PROGRESS_INTERVAL_MS));
+    let loaded = loop {
+        tokio::select! {
+            joined = &mut load => {
+                break joined.map_err(|e| format!("Embedding model init task failed: {}", e)).and_then(|r| r);
+            }
+            _ = &mut cancel_rx => {
+                break Err("Embedding model initialization cancelled".to_string());
+            }
+            _ = ticker.tick(), if downloading => {
+                set_phase(app, state, model_name, "downloading", dir_size(&cache_dir));
+            }
+        }
+    };
+    state.init_cancel.lock().unwrap().take();
+    let (model, dimension) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            let current = state.status.lock().unwrap().model_name.clone();
+            let phase = if state.embedder.lock().await.is_some() { "ready" } else { "idle" };
+            set_phase(app, state, &current, phase, dir_size(&cache_dir));
+            return Err(e);
+        }
+    };
+    *state.last_load_ms.lock().unwrap() = Some(started.elapsed().as_millis() as u64);
 
-    *embedder_lock = Some(model);
+    // Held until the indexes below are reloaded, so no embed pairs the new model
+    // with the old index
+    let mut embedder_lock = state.embedder.lock().await;
+    state.status.lock().unwrap().initialized = false;
+    // Indexes are replaced below (versions restart) and a new model embeds differently
+    state.search_cache.lock().unwrap().clear();
+    {
+        let mut status = state.status.lock().unwrap();
+        status.model_name = model_name.to_string();
+        status.dimension = dimension;
+    }
+    set_phase(app, state, model_name, "loading-index", dir_size(&cache_dir));
+    *state.index.lock().await = VectorIndex::new(dimension);
+    *state.notes.lock().await = VectorIndex::new(dimension);
+
+    *embedder_lock = Some(std::sync::Arc::new(model));
+
+    // An index built by a different model must be rebuilt
+    let marker = index_model_marker();
+    if let Ok(previous) = std::fs::read_to_string(&marker) {
+        if previous.trim() != model_name {
+            eprintln!("Embedding model changed ({} -> {}); clearing vector index", previous.trim(), model_name);
+            remove_index_files(&vectors_dir())?;
+            remove_index_files(&note_vectors_dir())?;
+        }
+    }
+    if let Err(e) = std::fs::create_dir_all(vectors_dir()).and_then(|_| std::fs::write(&marker, model_name)) {
+        eprintln!("Warning: Failed to record index model: {}", e);
+    }
+
+    match VectorIndex::load(&note_vectors_dir(), dimension) {
+        Ok(loaded) => *state.notes.lock().await = loaded,
+        Err(e) => eprintln!("Warning: Failed to load note index: {}", e),
+    }
+
+    match Bm25Index::load(&vectors_dir()) {
+        Ok(loaded) => *state.bm25.lock().await = loaded,
+        Err(e) => eprintln!("Warning: Failed to load BM25 index: {}", e),
+    }
+    *state.empty_sources.lock().unwrap() = load_empty_sources(&vectors_dir());
 
     // Load existing index from disk
-    let mut index_lock = state.index.lock().await;
-    match VectorIndex::load(&vectors_dir()) {
+    let mut count = match VectorIndex::load(&vectors_dir(), dimension) {
         Ok(loaded) => {
             let count = loaded.len();
-            *index_lock = loaded;
-
-            let mut status = state.status.lock().unwrap();
-            status.initialized = true;
-            status.chunks_indexed = count;
-            Ok(status.clone())
+            *state.index.lock().await = loaded;
+            count
         }
         Err(e) => {
             eprintln!("Warning: Failed to load vector index: {}", e);
-            let mut status = state.status.lock().unwrap();
-            status.initialized = true;
-            Ok(status.clone())
+            0
+        }
+    };
+
+    // A missing or partial bm25.json next to a populated vector index would
+    // silently leave hybrid search keyword-blind
+    let vault_path = app.state::<crate::AppState>().vault_path.lock().unwrap().clone();
+    if let Some(vault_path) = vault_path {
+        if state.bm25.lock().await.docs.len() < count {
+            let (rebuilt, dropped) = rebuild_missing_bm25(state, std::path::Path::new(&vault_path)).await;
+            eprintln!("BM25 index was incomplete: rebuilt {} chunks, dropped {} for reindex", rebuilt, dropped);
+            persist_indexes(state, dropped > 0).await;
+            count = state.index.lock().await.len();
+        }
+    }
+    {
+        let last_indexed = load_status()
+            .filter(|saved| saved.model_name == model_name)
+            .and_then(|saved| saved.last_indexed);
+        let mut status = state.status.lock().unwrap();
+        status.initialized = true;
+        status.chunks_indexed = count;
+        status.last_indexed = last_indexed;
+        save_status(&status);
+    }
+    set_phase(app, state, model_name, "ready", dir_size(&cache_dir));
+    let status = state.status.lock().unwrap().clone();
+    Ok(status)
+}
+
+/// Text of a chunk's stored byte region, if it can still be read as-is.
+fn read_chunk_text(root: &std::path::Path, meta: &ChunkMeta) -> Option<String> {
+    use std::io::{Read, Seek, SeekFrom};
+    let (offset, length) = (meta.offset?, meta.length?);
+    let mut file = std::fs::File::open(root.join(&meta.source)).ok()?;
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    let mut buf = vec![0; length as usize];
+    file.read_exact(&mut buf).ok()?;
+    let text = String::from_utf8(buf).ok()?;
+    (meta.content_hash.is_empty() || content_hash(&text) == meta.content_hash).then_some(text)
+}
+
+/// Re-create BM25 entries for live chunks that have none from their stored
+/// byte regions in the vault. Notes with a chunk that can't be recovered (no
+/// region, or the file changed) are dropped from the index so the next reindex
+/// re-embeds them. Returns (chunks rebuilt, chunks dropped); in memory only.
+async fn rebuild_missing_bm25(state: &SearchState, root: &std::path::Path) -> (usize, usize) {
+    let missing: Vec<ChunkMeta> = {
+        let index = state.index.lock().await;
+        let bm25 = state.bm25.lock().await;
+        index
+            .meta
+            .iter()
+            .enumerate()
+            .filter(|(i, m)| !index.dead[*i] && !bm25.docs.contains_key(&m.id))
+            .map(|(_, m)| m.clone())
+            .collect()
+    };
+
+    let mut lost: std::collections::HashSet<String> = std::collections::HashSet::new();
+    let mut rebuilt = 0;
+    {
+        let mut bm25 = state.bm25.lock().await;
+        for meta in &missing {
+            match read_chunk_text(root, meta) {
+                Some(text) => {
+                    bm25.upsert(&meta.id, &text);
+                    rebuilt += 1;
+                }
+                None => {
+                    lost.insert(meta.source.clone());
+                }
+            }
         }
     }
+    let dropped = if lost.is_empty() {
+        0
+    } else {
+        retain_chunks(state, |m| !lost.contains(&m.source)).await
+    };
+    (rebuilt, dropped)
+}
+
+/// Algorithm behind `content_hash`, reported by `content_hash_algorithm`.
+pub const CONTENT_HASH_ALGORITHM: &str = "sha256";
+
+/// Chunk content hash: lowercase hex SHA-256 of the UTF-8 text.
+fn content_hash(text: &str) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+/// Name of the chunk hash algorithm (lowercase hex digest of the UTF-8 text),
+/// so the frontend can compute hashes that match the stored ones.
+#[tauri::command]
+pub async fn content_hash_algorithm() -> Result<String, String> {
+    Ok(CONTENT_HASH_ALGORITHM.to_string())
 }
 
 /// Embed text chunks and store in the vector index.
 /// Accepts chunk IDs, texts, and metadata for incremental indexing.
+/// Chunks without a supplied hash (e.g. `content_hashes` empty) are hashed
+/// here with `content_hash`.
+/// `offsets`/`lengths` locate each chunk's bytes in its source file so previews
+/// can use `read_chunk_region` instead of reading the whole note.
+/// With `note_vectors`, also refreshes the note-level vector of every source in
+/// the batch (searched by `search_notes`); off by default to avoid extra storage.
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 pub async fn embed_chunks(
+    app: tauri::AppHandle,
     state: tauri::State<'_, SearchState>,
     ids: Vec<String>,
     texts: Vec<String>,
     sources: Vec<String>,
     content_hashes: Vec<String>,
     modified_ats: Vec<u64>,
+    offsets: Option<Vec<u64>>,
+    lengths: Option<Vec<u64>>,
+    headings: Option<Vec<Option<String>>>,
+    note_vectors: Option<bool>,
+) -> Result<usize, String> {
+    // Wait out a running reindex so its end-of-run save can't race these chunks
+    let _guard = state.begin_indexing().await;
+
+    // Build metadata
+    let meta: Vec<ChunkMeta> = ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| ChunkMeta {
+            id: id.clone(),
+            source: sources.get(i).cloned().unwrap_or_default(),
+            heading: headings.as_ref().and_then(|h| h.get(i).cloned().flatten()),
+            content_hash: content_hashes
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| texts.get(i).map(|t| content_hash(t)).unwrap_or_default()),
+            modified_at: modified_ats.get(i).copied().unwrap_or(0),
+            offset: offsets.as_ref().and_then(|o| o.get(i).copied()),
+            length: lengths.as_ref().and_then(|l| l.get(i).copied()),
+        })
+        .collect();
+
+    // Progress per sub-batch: embedding-progress { done, total } (chunks)
+    let on_progress = move |done: usize, total: usize| {
+        let _ = app.emit("embedding-progress", serde_json::json!({ "done": done, "total": total }));
+    };
+    let note_vectors = note_vectors.unwrap_or(false);
+    let count = index_chunks(&state, ids, texts, meta, note_vectors, on_progress).await?;
+    persist_indexes(&state, note_vectors).await;
+    Ok(count)
+}
+
+/// Write the chunk index, its BM25 index and the status to disk, plus the note
+/// index when `notes` is set. `index_chunks`/`retain_chunks` only change memory.
+async fn persist_indexes(state: &SearchState, notes: bool) {
+    let index_lock = state.index.lock().await;
+    save_status(&state.status.lock().unwrap());
+    if let Err(e) = index_lock.save(&vectors_dir()) {
+        eprintln!("Warning: Failed to save vector index: {}", e);
+    }
+    if let Err(e) = state.bm25.lock().await.save(&vectors_dir()) {
+        eprintln!("Warning: Failed to save BM25 index: {}", e);
+    }
+    if let Err(e) = save_empty_sources(&vectors_dir(), &state.empty_sources.lock().unwrap()) {
+        eprintln!("Warning: Failed to save empty sources: {}", e);
+    }
+    if notes {
+        if let Err(e) = state.notes.lock().await.save(&note_vectors_dir()) {
+            eprintln!("Warning: Failed to save note index: {}", e);
+        }
+    }
+}
+
+/// Embed `texts`, add them to the chunk index with `meta`, and (optionally)
+/// refresh the note-level vectors of every touched source. In memory only —
+/// callers persist with `persist_indexes`.
+async fn index_chunks(
+    state: &SearchState,
+    ids: Vec<String>,
+    texts: Vec<String>,
+    meta: Vec<ChunkMeta>,
+    note_vectors: bool,
+    on_progress: impl Fn(usize, usize) + Send + Sync + 'static,
 ) -> Result<usize, String> {
     let embedder_lock = state.embedder.lock().await;
     let embedder = embedder_lock
-        .as_ref()
+        .clone()
         .ok_or("Embedding model not initialized. Call init_embedding_model first.")?;
 
     if texts.is_empty() {
         return Ok(0);
     }
 
+    // Keyword index first: the texts move into the embedder
+    let mut bm25 = Bm25Index::default();
+    for (id, text) in ids.iter().zip(&texts) {
+        bm25.upsert(id, text);
+    }
+
     // Generate embeddings
-    let embeddings = embedder
-        .embed(texts.clone(), None)
-        .map_err(|e| format!("Embedding failed: {}", e))?;
+    let embeddings = state.embed_batched(embedder, texts, on_progress).await?;
 
     let count = embeddings.len();
-
-    // Build metadata
-    let meta: Vec<ChunkMeta> = ids
+    let source_times: std::collections::HashMap<String, u64> = meta
         .iter()
-        .enumerate()
-        .map(|(i, id)| ChunkMeta {
-            id: id.clone(),
-            source: sources.get(i).cloned().unwrap_or_default(),
-            heading: None,
-            content_hash: content_hashes.get(i).cloned().unwrap_or_default(),
-            modified_at: modified_ats.get(i).copied().unwrap_or(0),
-        })
+        .map(|m| (m.source.clone(), m.modified_at))
         .collect();
 
     // Add to index
     let mut index_lock = state.index.lock().await;
-    index_lock.add_batch(&ids, &embeddings, meta);
+    index_lock.add_batch(&ids, &embeddings, meta)?;
+
+    state.bm25.lock().await.merge(bm25);
 
     // Update status
     {
@@ -380,38 +1466,715 @@ pub async fn embed_chunks(
         );
     }
 
-    // Persist to disk
-    if let Err(e) = index_lock.save(&vectors_dir()) {
-        eprintln!("Warning: Failed to save vector index: {}", e);
+    if note_vectors {
+        let (note_ids, note_vecs): (Vec<String>, Vec<Vec<f32>>) = index_lock
+            .source_centroids()
+            .into_iter()
+            .filter(|(source, _)| source_times.contains_key(source))
+            .unzip();
+        let note_meta: Vec<ChunkMeta> = note_ids
+            .iter()
+            .map(|source| ChunkMeta {
+                id: source.clone(),
+                source: source.clone(),
+                heading: None,
+                content_hash: String::new(),
+                modified_at: source_times.get(source).copied().unwrap_or(0),
+                offset: None,
+                length: None,
+            })
+            .collect();
+
+        state.notes.lock().await.add_batch(&note_ids, &note_vecs, note_meta)?;
     }
 
     Ok(count)
 }
 
+/// Files re-chunked and embedded per `index_chunks` call during a reindex.
+const REINDEX_FILE_BATCH: usize = 50;
+
+/// Chunk size / overlap (approximate tokens) used by `reindex_vault`.
+const DEFAULT_CHUNK_MAX_TOKENS: usize = 512;
+const DEFAULT_CHUNK_OVERLAP: usize = 64;
+
+/// A markdown section between two ATX headings.
+struct Section<'a> {
+    heading: Option<String>,
+    /// Enclosing headings, outermost first, joined with " > " ("" before the first)
+    heading_path: String,
+    offset: usize,
+    text: &'a str,
+}
+
+/// Split markdown at ATX heading lines into sections, dropping blank ones.
+fn split_sections(content: &str) -> Vec<Section<'_>> {
+    let mut bounds: Vec<(usize, Option<String>, String)> = vec![(0, None, String::new())];
+    let mut trail: Vec<(usize, String)> = Vec::new();
+    let mut pos = 0;
+    for line in content.split_inclusive('\n') {
+        let rest = line.trim_start_matches('#');
+        if line.starts_with('#') && rest.starts_with(' ') {
+            let level = line.len() - rest.len();
+            let title = rest.trim().to_string();
+            trail.retain(|(l, _)| *l < level);
+            trail.push((level, title.clone()));
+            let path: Vec<&str> = trail.iter().map(|(_, t)| t.as_str()).collect();
+            bounds.push((pos, Some(title), path.join(" > ")));
+        }
+        pos += line.len();
+    }
+
+    bounds
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (start, heading, heading_path))| {
+            let end = bounds.get(i + 1).map(|b| b.0).unwrap_or(content.len());
+            let text = &content[*start..end];
+            if text.trim().is_empty() {
+                None
+            } else {
+                Some(Section {
+                    heading: heading.clone(),
+                    heading_path: heading_path.clone(),
+                    offset: *start,
+                    text,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Deterministic chunk ID: lowercase hex SHA-256 of
+/// `"<source>\n<heading path>\n<index>"`, where the heading path is the
+/// chunk's enclosing headings joined with `" > "` (empty before the first
+/// heading) and `index` counts chunks sharing that path within the file, from 0.
+/// Edits elsewhere in a note leave a chunk's ID unchanged, so `add_batch`
+/// updates it in place. Frontends chunking client-side should use the same scheme.
+fn chunk_id(source: &str, heading_path: &str, index: usize) -> String {
+    content_hash(&format!("{}\n{}\n{}", source, heading_path, index))
+}
+
+/// Split `text` into windows of at most `max_tokens` (~4 chars per token),
+/// each starting `overlap` tokens before the previous one ended. Windows end
+/// after whitespace where possible so words aren't cut. Returns (byte offset
+/// within `text`, window).
+fn split_window(text: &str, max_tokens: usize, overlap: usize) -> Vec<(usize, &str)> {
+    if crate::claude::estimate_tokens(text) <= max_tokens {
+        return vec![(0, text)];
+    }
+    let (max_chars, overlap_chars) = (max_tokens * 4, overlap * 4);
+    let mut bounds: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+    bounds.push(text.len());
+    let n = bounds.len() - 1;
+    let is_space = |c: usize| text[bounds[c]..bounds[c + 1]].starts_with(char::is_whitespace);
+
+    let mut windows = Vec::new();
+    let mut start = 0;
+    loop {
+        let mut end = (start + max_chars).min(n);
+        if end < n {
+            if let Some(ws) = (start + 1..end).rev().find(|&c| is_space(c)) {
+                end = ws + 1;
+            }
+        }
+        windows.push((bounds[start], &text[bounds[start]..bounds[end]]));
+        if end == n {
+            break;
+        }
+        // Step back by the overlap, then forward to the next word start
+        let mut next = end.saturating_sub(overlap_chars).max(start + 1);
+        if let Some(ws) = (next..end - 1).find(|&c| is_space(c)) {
+            next = ws + 1;
+        }
+        start = next;
+    }
+    windows
+}
+
+/// Headings first, then `split_window` for sections over `max_tokens`.
+/// IDs follow `chunk_id`.
+fn chunk_sections(content: &str, source: &str, max_tokens: usize, overlap: usize) -> Vec<Chunk> {
+    let mut per_path: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    let mut chunks = Vec::new();
+    for section in split_sections(content) {
+        for (start, text) in split_window(section.text, max_tokens, overlap) {
+            if text.trim().is_empty() {
+                continue;
+            }
+            let index = per_path.entry(section.heading_path.clone()).or_insert(0);
+            chunks.push(Chunk {
+                id: chunk_id(source, &section.heading_path, *index),
+                text: text.to_string(),
+                heading: section.heading.clone(),
+                offset: section.offset + start,
+            });
+            *index += 1;
+        }
+    }
+    chunks
+}
+
+/// Split markdown into embed-ready chunks: at headings first, then by
+/// approximate token count (~4 chars per token) with `overlap` tokens carried
+/// between consecutive pieces of a long section. Pass `source` (the note's
+/// vault-relative path) to get the same IDs `reindex_vault` stores (see `chunk_id`).
+#[tauri::command]
+pub async fn chunk_markdown(
+    content: String,
+    max_tokens: usize,
+    overlap: usize,
+    source: Option<String>,
+) -> Result<Vec<Chunk>, String> {
+    if max_tokens == 0 {
+        return Err("max_tokens must be at least 1".to_string());
+    }
+    if overlap >= max_tokens {
+        return Err("overlap must be smaller than max_tokens".to_string());
+    }
+    Ok(chunk_sections(&content, source.as_deref().unwrap_or(""), max_tokens, overlap))
+}
+
+/// Scan→diff→embed over a vault listing of (relative path, modified secs):
+/// drops chunks of files that are gone, then re-chunks (`chunk_sections`) and embeds
+/// every file whose stored modified time differs. The indexes are saved once,
+/// at the end of the run (also when it fails part-way). Callers should hold
+/// the indexing flag (`try_begin_indexing`).
+pub async fn reindex_vault(
+    state: &SearchState,
+    root: &std::path::Path,
+    files: Vec<(String, u64)>,
+    on_progress: impl Fn(usize, usize) + Send + Sync,
+) -> Result<ReindexSummary, String> {
+    if !state.status.lock().unwrap().initialized {
+        return Err("Embedding model not initialized.".to_string());
+    }
+
+    let present: std::collections::HashSet<&str> = files.iter().map(|(p, _)| p.as_str()).collect();
+    let chunks_removed = retain_chunks(state, |m| present.contains(m.source.as_str())).await;
+    state.empty_sources.lock().unwrap().retain(|path, _| present.contains(path.as_str()));
+
+    let mut indexed = state.index.lock().await.source_modified_times();
+    indexed.extend(state.empty_sources.lock().unwrap().iter().map(|(p, t)| (p.clone(), *t)));
+    let stale: Vec<&(String, u64)> = files
+        .iter()
+        .filter(|(path, modified)| indexed.get(path) != Some(modified))
+        .collect();
+    let note_vectors = state.notes.lock().await.len() > 0;
+
+    let result = reindex_stale(state, root, &stale, note_vectors, on_progress).await;
+    persist_indexes(state, true).await;
+
+    Ok(ReindexSummary {
+        files_scanned: files.len(),
+        files_reindexed: stale.len(),
+        chunks_removed,
+        chunks_embedded: result?,
+    })
+}
+
+/// Re-chunk and embed `stale` files in `REINDEX_FILE_BATCH` batches, replacing
+/// their old chunks. Returns the number of chunks embedded.
+async fn reindex_stale(
+    state: &SearchState,
+    root: &std::path::Path,
+    stale: &[&(String, u64)],
+    note_vectors: bool,
+    on_progress: impl Fn(usize, usize) + Send + Sync,
+) -> Result<usize, String> {
+    let total = stale.len();
+    let mut done = 0;
+    let mut chunks_embedded = 0;
+    on_progress(0, total);
+    for batch in stale.chunks(REINDEX_FILE_BATCH) {
+        let sources: std::collections::HashSet<&str> = batch.iter().map(|(p, _)| p.as_str()).collect();
+        retain_chunks(state, |m| !sources.contains(m.source.as_str())).await;
+
+        let (mut ids, mut texts, mut meta) = (Vec::new(), Vec::new(), Vec::new());
+        for (path, modified) in batch {
+            let content = match std::fs::read_to_string(root.join(path)) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            let chunks = chunk_sections(&content, path, DEFAULT_CHUNK_MAX_TOKENS, DEFAULT_CHUNK_OVERLAP);
+            {
+                let mut empty = state.empty_sources.lock().unwrap();
+                if chunks.is_empty() {
+                    empty.insert(path.clone(), *modified);
+                } else {
+                    empty.remove(path);
+                }
+            }
+            for chunk in chunks {
+                meta.push(ChunkMeta {
+                    id: chunk.id.clone(),
+                    source: path.clone(),
+                    heading: chunk.heading,
+                    content_hash: content_hash(&chunk.text),
+                    modified_at: *modified,
+                    offset: Some(chunk.offset as u64),
+                    length: Some(chunk.text.len() as u64),
+                });
+                ids.push(chunk.id);
+                texts.push(chunk.text);
+            }
+        }
+
+        chunks_embedded += index_chunks(state, ids, texts, meta, note_vectors, |_, _| {}).await?;
+        done += batch.len();
+        on_progress(done, total);
+    }
+    Ok(chunks_embedded)
+}
+
+/// Embed arbitrary texts with the loaded model and return the raw vectors.
+/// Does not touch the index — for external reuse (clustering, custom similarity).
+#[tauri::command]
+pub async fn embed_text(
+    state: tauri::State<'_, SearchState>,
+    texts: Vec<String>,
+) -> Result<Vec<Vec<f32>>, String> {
+    let embedder_lock = state.embedder.lock().await;
+    let embedder = embedder_lock
+        .clone()
+        .ok_or("Embedding model not initialized. Call init_embedding_model first.")?;
+
+    if texts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    state.embed_batched(embedder, texts, |_, _| {}).await
+}
+
+/// Drop every chunk (and the note-level vector) indexed under `source`, then
+/// persist. Returns the number of chunks removed.
+pub async fn remove_chunks_by_source(state: &SearchState, source: &str) -> usize {
+    let removed = retain_chunks(state, |m| m.source != source).await;
+    if removed > 0 {
+        persist_indexes(state, true).await;
+    }
+    removed
+}
+
+/// Keep only chunks matching `keep` in both indexes (in memory; see `persist_indexes`).
+async fn retain_chunks(state: &SearchState, keep: impl Fn(&ChunkMeta) -> bool) -> usize {
+    let mut index_lock = state.index.lock().await;
+    let removed = index_lock.retain(&keep);
+    if removed > 0 {
+        state.status.lock().unwrap().chunks_indexed = index_lock.len();
+        state.bm25.lock().await.retain(|id| index_lock.positions.contains_key(id));
+    }
+    state.notes.lock().await.retain(&keep);
+    removed
+}
+
+/// Wipe the vector store: empty both in-memory indexes, delete their files
+/// from `vectors_dir()`, and reset the indexed-chunk status.
+#[tauri::command]
+pub async fn clear_index(state: tauri::State<'_, SearchState>) -> Result<(), String> {
+    let _guard = state.try_begin_indexing()
+        .ok_or("Indexing is in progress; try again when it finishes")?;
+
+    state.index.lock().await.clear();
+    state.notes.lock().await.clear();
+    state.bm25.lock().await.clear();
+    state.empty_sources.lock().unwrap().clear();
+    remove_index_files(&vectors_dir())?;
+    remove_index_files(&note_vectors_dir())?;
+
+    let mut status = state.status.lock().unwrap();
+    status.chunks_indexed = 0;
+    status.last_indexed = None;
+    save_status(&status);
+    Ok(())
+}
+
+/// Of the given chunks, the IDs that must be (re-)embedded: not indexed yet,
+/// or indexed with a different content hash.
+#[tauri::command]
+pub async fn chunks_needing_reindex(
+    state: tauri::State<'_, SearchState>,
+    ids: Vec<String>,
+    content_hashes: Vec<String>,
+) -> Result<Vec<String>, String> {
+    if ids.len() != content_hashes.len() {
+        return Err(format!(
+            "Got {} ids but {} content hashes",
+            ids.len(),
+            content_hashes.len()
+        ));
+    }
+    let index_lock = state.index.lock().await;
+    Ok(ids
+        .into_iter()
+        .zip(content_hashes)
+        .filter(|(id, hash)| {
+            index_lock
+                .positions
+                .get(id)
+                .and_then(|&pos| index_lock.meta.get(pos))
+                .is_none_or(|m| &m.content_hash != hash)
+        })
+        .map(|(id, _)| id)
+        .collect())
+}
+
+/// Live chunk count per source note, largest first (ties by path).
+#[tauri::command]
+pub async fn index_stats(state: tauri::State<'_, SearchState>) -> Result<Vec<(String, usize)>, String> {
+    let index_lock = state.index.lock().await;
+    let mut counts: std::collections::HashMap<&str, usize> = std::collections::HashMap::new();
+    for (i, m) in index_lock.meta.iter().enumerate() {
+        if !index_lock.dead[i] {
+            *counts.entry(m.source.as_str()).or_insert(0) += 1;
+        }
+    }
+    let mut stats: Vec<(String, usize)> = counts.into_iter().map(|(s, n)| (s.to_string(), n)).collect();
+    stats.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(stats)
+}
+
+/// Rebuild the in-memory indexes without tombstoned (superseded) entries.
+/// Returns the number of tombstones dropped.
+#[tauri::command]
+pub async fn compact_vectors(state: tauri::State<'_, SearchState>) -> Result<usize, String> {
+    let dropped = state.index.lock().await.compact();
+    state.notes.lock().await.compact();
+    Ok(dropped)
+}
+
+/// Drop every chunk whose source is not in `valid_sources` (e.g. the relative
+/// paths from the latest `scan_vault`). Returns the number of chunks removed.
+#[tauri::command]
+pub async fn prune_vectors(
+    state: tauri::State<'_, SearchState>,
+    valid_sources: Vec<String>,
+) -> Result<usize, String> {
+    let valid: std::collections::HashSet<String> = valid_sources.into_iter().collect();
+    let removed = retain_chunks(&state, |m| valid.contains(&m.source)).await;
+    if removed > 0 {
+        persist_indexes(&state, true).await;
+    }
+    Ok(removed)
+}
+
+const MAX_SEARCH_CACHE_ENTRIES: usize = 256;
+
+/// MMR re-ranks this many candidates per requested result.
+const MMR_CANDIDATE_FACTOR: usize = 4;
+
 /// Search the vector index for chunks similar to the query text.
+/// `diversity` (MMR lambda, 0.0–1.0) trades relevance for source variety;
+/// None is plain cosine top-K. `source_prefix` limits results to notes under
+/// that vault path. Results are cached until the index changes.
 #[tauri::command]
 pub async fn search_vectors(
     state: tauri::State<'_, SearchState>,
     query: String,
     top_k: usize,
+    diversity: Option<f32>,
+    source_prefix: Option<String>,
 ) -> Result<Vec<VectorMatch>, String> {
-    let embedder_lock = state.embedder.lock().await;
-    let embedder = embedder_lock
-        .as_ref()
+    // Serve repeats from the cache while the index is unchanged
+    let key = SearchCacheKey {
+        query: query.clone(),
+        top_k,
+        diversity: diversity.map(f32::to_bits),
+        source_prefix: source_prefix.clone(),
+    };
+    let version = state.index.lock().await.version;
+    if let Some((cached_version, hits)) = state.search_cache.lock().unwrap().get(&key) {
+        if *cached_version == version {
+            return Ok(hits.clone());
+        }
+    }
+
+    let embedder = state
+        .embedder
+        .lock()
+        .await
+        .clone()
         .ok_or("Embedding model not initialized.")?;
+    let query_vec = &embed_query(embedder, query).await?;
+
+    // Search
+    let index_lock = state.index.lock().await;
+    if query_vec.len() != index_lock.dimension {
+        return Err(format!(
+            "Query embedding has {} dimensions but the index has {}; reindex required",
+            query_vec.len(),
+            index_lock.dimension
+        ));
+    }
+    let hits = match diversity {
+        Some(lambda) => index_lock.search_mmr(query_vec, top_k, lambda, source_prefix.as_deref()),
+        None => index_lock.search(query_vec, top_k, source_prefix.as_deref()),
+    };
+
+    let mut cache = state.search_cache.lock().unwrap();
+    if cache.len() >= MAX_SEARCH_CACHE_ENTRIES {
+        cache.clear();
+    }
+    cache.insert(key, (index_lock.version, hits.clone()));
+    Ok(hits)
+}
 
-    // Embed the query
-    let query_embeddings = embedder
-        .embed(vec![query], None)
-        .map_err(|e| format!("Query embedding failed: {}", e))?;
+/// Candidates taken from each ranking before fusing (× top_k).
+const HYBRID_CANDIDATE_FACTOR: usize = 4;
 
-    let query_vec = query_embeddings
-        .first()
-        .ok_or("Failed to generate query embedding")?;
+/// Keyword + semantic search. Candidates from BM25 and cosine ranking are
+/// pooled; each score is divided by its best candidate score and the two are
+/// combined as `alpha · cosine + (1 − alpha) · bm25` (alpha defaults to 0.5).
+#[tauri::command]
+pub async fn search_hybrid(
+    state: tauri::State<'_, SearchState>,
+    query: String,
+    top_k: usize,
+    alpha: Option<f32>,
+) -> Result<Vec<VectorMatch>, String> {
+    let alpha = alpha.unwrap_or(0.5).clamp(0.0, 1.0);
+    let candidates = top_k.saturating_mul(HYBRID_CANDIDATE_FACTOR);
+
+    let embedder = state
+        .embedder
+        .lock()
+        .await
+        .clone()
+        .ok_or("Embedding model not initialized.")?;
+    let query_vec = &embed_query(embedder, query.clone()).await?;
 
-    // Search
     let index_lock = state.index.lock().await;
-    Ok(index_lock.search(query_vec, top_k))
+    if query_vec.len() != index_lock.dimension {
+        return Err(format!(
+            "Query embedding has {} dimensions but the index has {}; reindex required",
+            query_vec.len(),
+            index_lock.dimension
+        ));
+    }
+
+    // slot → (cosine, bm25)
+    let mut pooled: std::collections::HashMap<usize, (f32, f32)> = index_lock
+        .top_scores(query_vec, candidates, None)
+        .into_iter()
+        .map(|(i, cos)| (i, (cos, 0.0)))
+        .collect();
+    for (id, score) in state.bm25.lock().await.search(&query, candidates) {
+        let Some(&i) = index_lock.positions.get(&id) else { continue };
+        pooled
+            .entry(i)
+            .or_insert_with(|| (index_lock.query_cosine(query_vec, i).max(0.0), 0.0))
+            .1 = score;
+    }
+
+    let max_cos = pooled.values().map(|s| s.0).fold(0.0, f32::max);
+    let max_bm25 = pooled.values().map(|s| s.1).fold(0.0, f32::max);
+    let normalize = |v: f32, max: f32| if max > 0.0 { v / max } else { 0.0 };
+
+    let mut ranked: Vec<(usize, f32)> = pooled
+        .into_iter()
+        .map(|(i, (cos, bm25))| {
+            (i, alpha * normalize(cos, max_cos) + (1.0 - alpha) * normalize(bm25, max_bm25))
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    ranked.truncate(top_k);
+
+    Ok(ranked.into_iter().map(|(i, s)| index_lock.to_match(i, s)).collect())
+}
+
+/// Compare the model's embedding dimension with the index's, so the UI can
+/// offer a reindex instead of showing empty results.
+#[tauri::command]
+pub async fn check_search_ready(state: tauri::State<'_, SearchState>) -> Result<SearchReadiness, String> {
+    let (model_initialized, model_dimension) = {
+        let status = state.status.lock().unwrap();
+        (status.initialized, status.dimension)
+    };
+    let memory_dimension = state.index.lock().await.dimension;
+    let stored = VectorIndex::stored_header(&vectors_dir()).filter(|(_, count)| *count > 0);
+    let index_dimension = stored.map(|(dim, _)| dim).unwrap_or(memory_dimension);
+
+    let message = if !model_initialized {
+        Some("Embedding model not initialized".to_string())
+    } else if index_dimension != model_dimension || memory_dimension != model_dimension {
+        Some(format!(
+            "Index was built with {}-dimensional vectors but the model produces {}; reindex to search",
+            index_dimension, model_dimension
+        ))
+    } else {
+        None
+    };
+
+    Ok(SearchReadiness {
+        model_initialized,
+        model_dimension,
+        index_dimension,
+        compatible: message.is_none(),
+        message,
+    })
+}
+
+/// Drop all cached `search_vectors` results.
+#[tauri::command]
+pub async fn clear_search_cache(state: tauri::State<'_, SearchState>) -> Result<(), String> {
+    state.search_cache.lock().unwrap().clear();
+    Ok(())
+}
+
+/// Search the note-level index ("related notes"). Match IDs are source paths.
+/// Empty until `embed_chunks` has run with `note_vectors` enabled.
+#[tauri::command]
+pub async fn search_notes(
+    state: tauri::State<'_, SearchState>,
+    query: String,
+    top_k: usize,
+) -> Result<Vec<VectorMatch>, String> {
+    let embedder = state
+        .embedder
+        .lock()
+        .await
+        .clone()
+        .ok_or("Embedding model not initialized.")?;
+    let query_vec = &embed_query(embedder, query).await?;
+
+    let notes_lock = state.notes.lock().await;
+    Ok(notes_lock.search(query_vec, top_k, None))
+}
+
+/// Upper bound on notes considered by `find_duplicates`.
+const MAX_DUPLICATE_SOURCES: usize = 20_000;
+
+/// SimHash banding for `find_duplicates`: each band hashes a note's vector
+/// against `DUPLICATE_LSH_BITS` random hyperplanes. Two notes at cosine 0.9
+/// share at least one of 24 ten-bit bands with probability > 0.99.
+const DUPLICATE_LSH_BANDS: usize = 24;
+const DUPLICATE_LSH_BITS: usize = 10;
+
+/// Note pairs sharing at least one SimHash band, i.e. the only pairs worth
+/// scoring. Hyperplanes come from a fixed seed so results are repeatable.
+fn duplicate_candidates(vectors: &[&[f32]]) -> std::collections::HashSet<(usize, usize)> {
+    let dim = vectors.first().map(|v| v.len()).unwrap_or(0);
+    let mut seed: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut next = move || {
+        // xorshift64*, summed thrice for a roughly normal component
+        (0..3)
+            .map(|_| {
+                seed ^= seed >> 12;
+                seed ^= seed << 25;
+                seed ^= seed >> 27;
+                (seed.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40) as f32 / (1u64 << 24) as f32
+            })
+            .sum::<f32>()
+            - 1.5
+    };
+    let planes: Vec<Vec<f32>> = (0..DUPLICATE_LSH_BANDS * DUPLICATE_LSH_BITS)
+        .map(|_| (0..dim).map(|_| next()).collect())
+        .collect();
+
+    let mut pairs = std::collections::HashSet::new();
+    for band in planes.chunks(DUPLICATE_LSH_BITS) {
+        let mut buckets: std::collections::HashMap<u32, Vec<usize>> = std::collections::HashMap::new();
+        for (i, v) in vectors.iter().enumerate() {
+            let signature = band.iter().enumerate().fold(0u32, |sig, (bit, plane)| {
+                let dot: f32 = plane.iter().zip(v.iter()).map(|(p, x)| p * x).sum();
+                if dot >= 0.0 { sig | (1 << bit) } else { sig }
+            });
+            buckets.entry(signature).or_default().push(i);
+        }
+        for members in buckets.values() {
+            for (k, &a) in members.iter().enumerate() {
+                for &b in &members[k + 1..] {
+                    pairs.insert((a, b));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+/// Find near-duplicate notes: bucket note-level (averaged chunk) vectors with
+/// SimHash, score the pairs sharing a bucket, and group every pair scoring at
+/// least `threshold` into clusters. Bucketing is approximate: at low thresholds
+/// some qualifying pairs may be missed. Only the `MAX_DUPLICATE_SOURCES` most
+/// recently indexed notes are considered.
+#[tauri::command]
+pub async fn find_duplicates(
+    state: tauri::State<'_, SearchState>,
+    threshold: f32,
+) -> Result<Vec<DuplicateCluster>, String> {
+    let index_lock = state.index.lock().await;
+    let mut notes = index_lock.source_centroids();
+    drop(index_lock);
+    if notes.len() > MAX_DUPLICATE_SOURCES {
+        notes.drain(..notes.len() - MAX_DUPLICATE_SOURCES);
+    }
+
+    tokio::task::spawn_blocking(move || duplicate_clusters(&notes, threshold))
+        .await
+        .map_err(|e| format!("Duplicate search failed: {}", e))
+}
+
+fn duplicate_clusters(notes: &[(String, Vec<f32>)], threshold: f32) -> Vec<DuplicateCluster> {
+    // Union-find over notes linked by a similarity >= threshold
+    let mut parent: Vec<usize> = (0..notes.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let vectors: Vec<&[f32]> = notes.iter().map(|(_, v)| v.as_slice()).collect();
+    let mut best = vec![0.0f32; notes.len()];
+    for (a, b) in duplicate_candidates(&vectors) {
+        // Centroids are unit length, so the dot product is the cosine
+        let score: f32 = vectors[a].iter().zip(vectors[b]).map(|(x, y)| x * y).sum();
+        if score >= threshold {
+            let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+            parent[ra] = rb;
+            best[a] = best[a].max(score);
+            best[b] = best[b].max(score);
+        }
+    }
+
+    let mut clusters: std::collections::HashMap<usize, DuplicateCluster> = std::collections::HashMap::new();
+    for i in 0..notes.len() {
+        if best[i] == 0.0 {
+            continue;
+        }
+        let r = root(&mut parent, i);
+        let cluster = clusters.entry(r).or_insert_with(|| DuplicateCluster {
+            sources: Vec::new(),
+            max_score: 0.0,
+        });
+        cluster.sources.push(notes[i].0.clone());
+        cluster.max_score = cluster.max_score.max(best[i]);
+    }
+
+    let mut result: Vec<DuplicateCluster> = clusters.into_values().collect();
+    result.sort_by(|a, b| b.max_score.partial_cmp(&a.max_score).unwrap_or(std::cmp::Ordering::Equal));
+    result
+}
+
+/// Report the model's cache location, whether its weights are downloaded,
+/// and the last measured load time.
+#[tauri::command]
+pub async fn embedding_model_info(
+    state: tauri::State<'_, SearchState>,
+) -> Result<EmbeddingModelInfo, String> {
+    let model_name = state.status.lock().unwrap().model_name.clone();
+    let (_, model) = resolve_embedding_model(&model_name)?;
+    let (model_dir, present) = model_dir(&model)?;
+
+    Ok(EmbeddingModelInfo {
+        model_name,
+        cache_dir: model_cache_dir().to_string_lossy().to_string(),
+        model_dir: model_dir.to_string_lossy().to_string(),
+        present,
+        size_bytes: dir_size(&model_dir),
+        last_load_ms: *state.last_load_ms.lock().unwrap(),
+    })
 }
 
 /// Get the current embedding engine status.
@@ -421,3 +2184,47 @@ pub async fn get_embedding_status(
 ) -> Result<EmbeddingStatus, String> {
     Ok(state.status.lock().unwrap().clone())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(id: &str) -> ChunkMeta {
+        ChunkMeta {
+            id: id.to_string(),
+            source: format!("{}.md", id),
+            heading: None,
+            content_hash: String::new(),
+            modified_at: 0,
+            offset: None,
+            length: None,
+        }
+    }
+
+    /// Timing for the incremental-reindex path: re-adding existing chunks in
+    /// `REINDEX_FILE_BATCH`-sized batches. Run with
+    /// `cargo test --release -- --ignored --nocapture bench_add_batch`.
+    #[test]
+    #[ignore]
+    fn bench_add_batch_updates() {
+        const DIM: usize = 384;
+        const CHUNKS: usize = 20_000;
+        let ids: Vec<String> = (0..CHUNKS).map(|i| format!("{:064x}", i)).collect();
+        let vectors: Vec<Vec<f32>> = (0..CHUNKS).map(|i| vec![i as f32 + 1.0; DIM]).collect();
+        let mut index = VectorIndex::new(DIM);
+        let add = |index: &mut VectorIndex, range: std::ops::Range<usize>| {
+            let metas = ids[range.clone()].iter().map(|id| meta(id)).collect();
+            index.add_batch(&ids[range.clone()], &vectors[range], metas).unwrap();
+        };
+
+        for start in (0..CHUNKS).step_by(REINDEX_FILE_BATCH) {
+            add(&mut index, start..(start + REINDEX_FILE_BATCH).min(CHUNKS));
+        }
+        let started = std::time::Instant::now();
+        for start in (0..CHUNKS).step_by(REINDEX_FILE_BATCH) {
+            add(&mut index, start..(start + REINDEX_FILE_BATCH).min(CHUNKS));
+        }
+        println!("re-added {} of {} chunks in {:?}", CHUNKS, index.len(), started.elapsed());
+        assert_eq!(index.len(), CHUNKS);
+    }
+}
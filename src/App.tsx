@@ -57,6 +57,9 @@ export default function App() {
 
   // ── Multi-tab state ─────────────────────────────────────────────────────
   const tabState = useTabs({ chat, aiTitle: aiTitleRef.current });
+  useEffect(() => {
+    chat.setLocalSessionId(tabState.activeTabId);
+  }, [chat.setLocalSessionId, tabState.activeTabId]);
 
   // ── Chat context for ChatProvider ─────────────────────────────────────────
   const chatState = useMemo(() => ({
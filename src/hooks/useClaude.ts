@@ -165,6 +165,12 @@ export function useClaude(
   const searchContextRef = useRef(searchRelevantContext);
   searchContextRef.current = searchRelevantContext;
 
+  /** ThunderClaude session (tab) the chat belongs to — lets send_query refuse read-only sessions. */
+  const localSessionIdRef = useRef<string | null>(null);
+  const setLocalSessionId = useCallback((id: string | null) => {
+    localSessionIdRef.current = id;
+  }, []);
+
   const activeQueryRef = useRef<string | null>(null);
   const activeEngineRef = useRef<"claude" | "gemini">("claude");
   const activeModelRef = useRef<string>(model);
@@ -336,6 +342,7 @@ export function useClaude(
             session_id: canResume ? engineSessionId : null,
             resume: canResume,
             permission_mode: permissionMode === "default" ? null : permissionMode,
+            local_session_id: localSessionIdRef.current,
           },
         });
         activeQueryRef.current = queryId;
@@ -886,6 +893,7 @@ export function useClaude(
     cancelQuery,
     newChat,
     loadSession,
+    setLocalSessionId,
     compactMessages,
     trimMessages,
     injectSystemMessage,